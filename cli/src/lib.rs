@@ -1,72 +1,885 @@
+mod output;
 mod placeholders;
 
-use clap::{Parser, Subcommand};
+pub use output::OutputFormat;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use output::NdjsonObserver;
+use pgmt_core::{
+    ConfigError, ConnectOptions, DiscoveryOptions, Error, ErrorCategory, FlywayConfig, LintOptions,
+    MigrationState, MigrationVersion, Migrator, NewMigrationOptions, Pool, Result, SessionSettings,
+    SquashOptions, TransactionMode,
+};
 use placeholders::collect_placeholders_from_environment_variable;
-// use std::env;
-
-pub async fn run(cli: Cli) {
-    // println!("Hello, from PGMT!");
-
-    // match env::current_dir() {
-    //     Ok(path) => println!("Current working directory: {}", path.display()),
-    //     Err(e) => eprintln!("Error getting current directory: {}", e),
-    // }
-
-    // match cli.command {
-    //     Commands::Migrate {
-    //         url, directories, ..
-    //     } => {
-    //         println!("URL: {}", url);
-    //         for dir in directories {
-    //             println!("Directory: {}", dir);
-    //         }
-    //     }
-    // }
-    match cli.command {
-        Commands::Migrate { url, directories } => {
-            let placeholders = collect_placeholders_from_environment_variable();
-            println!("URL: {}", url);
-            for dir in directories.clone() {
-                println!("Directory: {}", dir);
-            }
-            pgmt_core::migration_dirs(directories, url, placeholders)
-                .await
-                .unwrap();
+use std::error::Error as _;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_postgres::error::SqlState;
+
+/// A command failed for a reason not covered by the other exit codes.
+pub const EXIT_FAILURE: u8 = 1;
+/// Invalid command line arguments or configuration, the exit code clap uses.
+pub const EXIT_USAGE: u8 = 2;
+/// Unable to connect to the database.
+pub const EXIT_CONNECTION: u8 = 3;
+/// The migration files or the history table are not valid, nothing was
+/// migrated.
+pub const EXIT_VALIDATION: u8 = 4;
+/// The SQL of a migration or hook failed.
+pub const EXIT_MIGRATION: u8 = 5;
+/// Unable to take the migration lock, or a statement gave up waiting for a
+/// lock, see `lock_timeout`.
+pub const EXIT_LOCK_TIMEOUT: u8 = 6;
+
+const HISTORY_TABLE: &str = "_schema_history";
+
+pub async fn run(cli: Cli) -> ExitCode {
+    let output = cli.output;
+    let command = cli.command.name();
+    match execute(cli.command, output).await {
+        Ok(code) => ExitCode::from(code),
+        Err(error) => {
+            if output.is_text() {
+                eprintln!("Error: {error}");
+                if cli.verbose {
+                    let mut source = error.source();
+                    while let Some(cause) = source {
+                        eprintln!("Caused by: {cause}");
+                        source = cause.source();
+                    }
+                }
+            } else {
+                output.error(command, &error);
+            }
+            ExitCode::from(exit_code(&error))
         }
     }
 }
 
+fn exit_code(error: &Error) -> u8 {
+    if error.sql_state() == Some(SqlState::LOCK_NOT_AVAILABLE.code()) {
+        return EXIT_LOCK_TIMEOUT;
+    }
+    match error.category() {
+        ErrorCategory::Config => EXIT_USAGE,
+        ErrorCategory::Connection => EXIT_CONNECTION,
+        ErrorCategory::Discovery
+        | ErrorCategory::Validation
+        | ErrorCategory::Checksum
+        | ErrorCategory::Ordering
+        | ErrorCategory::Template
+        | ErrorCategory::HistoryCorruption => EXIT_VALIDATION,
+        ErrorCategory::Execution => EXIT_MIGRATION,
+        ErrorCategory::Lock => EXIT_LOCK_TIMEOUT,
+        ErrorCategory::Database => EXIT_FAILURE,
+    }
+}
+
+async fn execute(mut command: Commands, output: OutputFormat) -> Result<u8> {
+    let name = command.name();
+    command.load_flyway_config()?;
+    match command {
+        Commands::Migrate { args } => {
+            if output.is_text() && !args.connection.url.is_empty() {
+                println!("URL: {}", pgmt_core::redact_password(&args.connection.url));
+                for dir in args.directories.clone() {
+                    println!("Directory: {}", dir);
+                }
+            }
+            let pool = args.connection.connect(Some(0)).await?;
+            let mut migrator = args.migrator()?;
+            if output == OutputFormat::Ndjson {
+                migrator = migrator.observer(Arc::new(NdjsonObserver));
+            }
+            let report = migrator.migrate(&pool).await?;
+            if !output.is_text() {
+                output.result(name, "ok", &report);
+                return Ok(0);
+            }
+            for warning in &report.warnings {
+                println!("Warning: {warning}");
+            }
+            if report.applied.is_empty() {
+                println!("Nothing to migrate");
+            }
+            for migration in report.applied {
+                println!(
+                    "Applied {} ({} ms)",
+                    migration.script, migration.execution_time
+                );
+            }
+        }
+        Commands::Info { args } => {
+            let pool = args.connection.connect(Some(0)).await?;
+            let report = args.migrator()?.info(&pool).await?;
+            if !output.is_text() {
+                output.result(name, "ok", &report);
+                return Ok(0);
+            }
+            for migration in report.migrations {
+                println!(
+                    "{:<16} {:<14} {}",
+                    migration.version.unwrap_or_default(),
+                    state_name(migration.state),
+                    migration.script
+                );
+            }
+        }
+        Commands::Validate { args } => {
+            let pool = args.connection.connect(Some(0)).await?;
+            let report = args.migrator()?.validate(&pool).await?;
+            if !output.is_text() {
+                let status = if report.is_valid() { "ok" } else { "invalid" };
+                output.result(name, status, &report);
+            } else {
+                for issue in &report.issues {
+                    println!("{issue}");
+                }
+                if report.is_valid() {
+                    println!("Validated, {} pending migration(s)", report.pending.len());
+                }
+            }
+            if !report.is_valid() {
+                return Ok(EXIT_VALIDATION);
+            }
+        }
+        Commands::Undo { args } => {
+            let pool = args.connection.connect(Some(0)).await?;
+            let mut migrator = args.migrator()?;
+            if output == OutputFormat::Ndjson {
+                migrator = migrator.observer(Arc::new(NdjsonObserver));
+            }
+            let report = migrator.undo(&pool).await?;
+            if !output.is_text() {
+                output.result(name, "ok", &report);
+                return Ok(0);
+            }
+            for migration in report.undone {
+                println!(
+                    "Undone {} ({} ms)",
+                    migration.script, migration.execution_time
+                );
+            }
+        }
+        Commands::Repair { args } => {
+            let pool = args.connection.connect(Some(0)).await?;
+            let report = args.migrator()?.repair(&pool).await?;
+            if !output.is_text() {
+                output.result(name, "ok", &report);
+                return Ok(0);
+            }
+            for script in report.removed_failed {
+                println!("Removed failed migration {script}");
+            }
+            for script in report.realigned {
+                println!("Realigned checksum of {script}");
+            }
+            for script in report.deleted {
+                println!("Marked missing migration {script} as deleted");
+            }
+        }
+        Commands::New {
+            description,
+            dir,
+            timestamp,
+            with_undo,
+            template,
+            undo_template,
+        } => {
+            let options = NewMigrationOptions {
+                timestamp,
+                with_undo,
+                template: template.as_deref().map(read_template).transpose()?,
+                undo_template: undo_template.as_deref().map(read_template).transpose()?,
+            };
+            let migration = pgmt_core::new_migration(&dir, &description, &options)?;
+            if !output.is_text() {
+                output.result(name, "ok", &migration);
+                return Ok(0);
+            }
+            for file in migration.files {
+                println!("Created {file}");
+            }
+        }
+        Commands::Lint { args } => {
+            let migrator = args.migrator()?;
+            let options = LintOptions {
+                allow: args.allow.clone(),
+            };
+            let report = match &args.url {
+                Some(url) => {
+                    let pool = pgmt_core::connect(url.clone()).await?;
+                    migrator.lint_pending(&pool, &options).await?
+                }
+                None => migrator.lint(&options)?,
+            };
+            let status = if report.is_clean() { "ok" } else { "invalid" };
+            if args.sarif {
+                output::print_sarif(&report);
+            } else if !output.is_text() {
+                output.result(name, status, &report);
+            } else {
+                for finding in &report.findings {
+                    println!(
+                        "{}:{}: {} ({})",
+                        finding.file_path, finding.line, finding.message, finding.rule
+                    );
+                }
+                if report.is_clean() {
+                    println!(
+                        "Checked {} migration(s), no issues found",
+                        report.checked.len()
+                    );
+                }
+            }
+            if !report.is_clean() {
+                return Ok(EXIT_VALIDATION);
+            }
+        }
+        Commands::Squash {
+            args,
+            up_to,
+            dir,
+            description,
+            archive,
+            pg_dump,
+        } => {
+            let options = SquashOptions {
+                dir,
+                description,
+                archive,
+                pg_dump,
+                connect: args.connection.connect_options(Some(0)),
+            };
+            let report = args
+                .migrator()?
+                .squash(&args.connection.url, &up_to, &options)
+                .await?;
+            if !output.is_text() {
+                output.result(name, "ok", &report);
+                return Ok(0);
+            }
+            println!(
+                "Squashed {} migration(s) into {}",
+                report.squashed.len(),
+                report.baseline
+            );
+            for path in report.archived {
+                println!("Archived {path}");
+            }
+        }
+        Commands::ImportHistory {
+            args,
+            from: HistoryFormat::Flyway,
+            from_table,
+        } => {
+            let pool = args.connection.connect(Some(0)).await?;
+            let from_table = args.connection.flyway_table(from_table);
+            let report = args.migrator()?.import_history(&pool, &from_table).await?;
+            if !output.is_text() {
+                output.result(name, "ok", &report);
+                return Ok(0);
+            }
+            println!(
+                "Imported {} row(s) from {} into {}",
+                report.copied.len(),
+                report.from,
+                report.to
+            );
+        }
+        Commands::ExportHistory {
+            args,
+            to: HistoryFormat::Flyway,
+            to_table,
+        } => {
+            let pool = args.connection.connect(Some(0)).await?;
+            let to_table = args.connection.flyway_table(to_table);
+            let report = args.migrator()?.export_history(&pool, &to_table).await?;
+            if !output.is_text() {
+                output.result(name, "ok", &report);
+                return Ok(0);
+            }
+            println!(
+                "Exported {} row(s) from {} into {}",
+                report.copied.len(),
+                report.from,
+                report.to
+            );
+        }
+        Commands::Wait { connection } => {
+            connection.connect(None).await?;
+            if !output.is_text() {
+                output.result(name, "ok", &serde_json::json!({ "ready": true }));
+                return Ok(0);
+            }
+            println!("The database is ready");
+        }
+        Commands::TestDb {
+            command: TestDbCommands::Gc { url, older_than },
+        } => {
+            let url = url
+                .or_else(|| std::env::var("PGMT_TEST_DB_URL").ok())
+                .unwrap_or_default();
+            let pool = pgmt_core::connect(url).await?;
+            let report = pgmt_core::drop_test_databases(&pool, older_than).await?;
+            if !output.is_text() {
+                output.result(name, "ok", &report);
+                return Ok(0);
+            }
+            if report.dropped.is_empty() {
+                println!("No test databases to drop");
+            }
+            for db_name in report.dropped {
+                println!("Dropped {db_name}");
+            }
+        }
+        Commands::Baseline {
+            connection,
+            table,
+            baseline_version,
+        } => {
+            let pool = connection.connect(Some(0)).await?;
+            let config = &connection.flyway;
+            let baseline_version = baseline_version
+                .or_else(|| config.baseline_version.clone())
+                .unwrap_or_else(|| "1".to_string());
+            let report = Migrator::new()
+                .history_table(table.unwrap_or_else(|| config.qualify(HISTORY_TABLE)))
+                .baseline_version(&baseline_version)?
+                .baseline(&pool)
+                .await?;
+            if !output.is_text() {
+                output.result(name, "ok", &report);
+                return Ok(0);
+            }
+            println!("Baselined at version {}", report.version);
+        }
+    }
+    Ok(0)
+}
+
+fn read_template(path: &str) -> Result<String> {
+    std::fs::read_to_string(path).map_err(|e| {
+        ConfigError {
+            message: format!("Unable to read the template {path}: {e}"),
+            source: Some(Box::new(e)),
+        }
+        .into()
+    })
+}
+
+fn state_name(state: MigrationState) -> &'static str {
+    match state {
+        MigrationState::Applied => "Applied",
+        MigrationState::Pending => "Pending",
+        MigrationState::AboveTarget => "Above target",
+        MigrationState::BelowBaseline => "Below baseline",
+        MigrationState::Ignored => "Ignored",
+        MigrationState::Missing => "Missing",
+        MigrationState::Failed => "Failed",
+        MigrationState::Baseline => "Baseline",
+    }
+}
+
 #[derive(Parser)]
 #[command(name = "pgmt")]
 #[command(about = "PostgreSQL Migration Tool")]
 pub struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Print text, a JSON document or NDJSON events to stdout
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+
+    /// Print the causes of errors
+    #[arg(short, long, global = true)]
+    verbose: bool,
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
     /// Run database migrations from one or more directories
     Migrate {
-        /// Database URL
+        #[command(flatten)]
+        args: MigratorArgs,
+    },
+    /// Show the state of every migration
+    Info {
+        #[command(flatten)]
+        args: MigratorArgs,
+    },
+    /// Verify the applied migrations against the migration files
+    Validate {
+        #[command(flatten)]
+        args: MigratorArgs,
+    },
+    /// Undo the latest migration, or every migration above the target
+    Undo {
+        #[command(flatten)]
+        args: MigratorArgs,
+    },
+    /// Fix the history table after failed or changed migrations
+    Repair {
+        #[command(flatten)]
+        args: MigratorArgs,
+    },
+    /// Mark an existing database as migrated up to the baseline version
+    Baseline {
+        #[command(flatten)]
+        connection: ConnectArgs,
+
+        /// Table keeping track of the applied migrations, `_schema_history` by default
+        #[arg(long)]
+        table: Option<String>,
+
+        /// Version to baseline the database at, `1` by default
+        #[arg(long, value_parser = parse_version)]
+        baseline_version: Option<String>,
+    },
+    /// Squash the migrations up to a version into a baseline migration
+    Squash {
+        #[command(flatten)]
+        args: MigratorArgs,
+
+        /// Squash the migrations up to and including this version
+        #[arg(long, value_parser = parse_version)]
+        up_to: String,
+
+        /// Directory to create the baseline migration in, defaults to the first migration directory
+        #[arg(short = 'd', long)]
+        dir: Option<String>,
+
+        /// Description of the baseline migration
+        #[arg(long)]
+        description: Option<String>,
+
+        /// Move the squashed migrations to this directory
+        #[arg(long, value_name = "DIR")]
+        archive: Option<String>,
+
+        /// The pg_dump to dump the schema with
+        #[arg(long, value_name = "PATH")]
+        pg_dump: Option<String>,
+    },
+    /// Create the next migration file
+    New {
+        /// What the migration does, like "add users table"
+        description: String,
+
+        /// Directory to create the migration in
+        #[arg(short = 'd', long, default_value = "migrations")]
+        dir: String,
+
+        /// Version the migration with the current UTC time, like 20240105102030
+        #[arg(long)]
+        timestamp: bool,
+
+        /// Also create the undo migration
+        #[arg(long)]
+        with_undo: bool,
+
+        /// File the migration is created from, `${version}` and `${description}` are replaced
+        #[arg(long, value_name = "PATH")]
+        template: Option<String>,
+
+        /// File the undo migration is created from
+        #[arg(long, value_name = "PATH")]
+        undo_template: Option<String>,
+    },
+    /// Check migrations for operations that block tables or break the running application
+    Lint {
+        #[command(flatten)]
+        args: LintArgs,
+    },
+    /// Copy the history table of another migration tool into the history table
+    ImportHistory {
+        #[command(flatten)]
+        args: MigratorArgs,
+
+        /// Migration tool the database was migrated with
+        #[arg(long, value_enum)]
+        from: HistoryFormat,
+
+        /// History table of the other tool, `flyway_schema_history` by default
+        #[arg(long)]
+        from_table: Option<String>,
+    },
+    /// Copy the history table into the history table of another migration tool
+    ExportHistory {
+        #[command(flatten)]
+        args: MigratorArgs,
+
+        /// Migration tool taking over the database
+        #[arg(long, value_enum)]
+        to: HistoryFormat,
+
+        /// History table of the other tool, `flyway_schema_history` by default
+        #[arg(long)]
+        to_table: Option<String>,
+    },
+    /// Wait until the database accepts connections
+    Wait {
+        #[command(flatten)]
+        connection: ConnectArgs,
+    },
+    /// Manage the databases created by the test helpers
+    TestDb {
+        #[command(subcommand)]
+        command: TestDbCommands,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TestDbCommands {
+    /// Drop the test databases left behind by killed test runs, and stale templates
+    Gc {
+        /// Server of the test databases, defaults to PGMT_TEST_DB_URL and the PG* environment variables
         #[arg(short = 'u', long)]
-        url: String,
+        url: Option<String>,
 
-        /// Directories containing migrations
-        #[arg(required = true)]
-        directories: Vec<String>,
+        /// Only drop test databases created, and templates built, longer ago than this, like 30m, 1h or 2d
+        #[arg(long, value_name = "AGE", value_parser = parse_age)]
+        older_than: Duration,
     },
 }
 
-// fn main() {
-//     let cli = Cli::parse();
-//
-//     match cli.command {
-//         Commands::Migrate { url, directories } => {
-//             println!("URL: {}", url);
-//             for dir in directories {
-//                 println!("Directory: {}", dir);
-//             }
-//         }
-//     }
-// }
+/// Parses an age like `90s`, `30m`, `1h` or `2d`.
+fn parse_age(age: &str) -> core::result::Result<Duration, String> {
+    let unit = age.trim_start_matches(|c: char| c.is_ascii_digit());
+    let amount: u64 = age[..age.len() - unit.len()]
+        .parse()
+        .map_err(|_| format!("`{age}` is not a number followed by s, m, h or d"))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("`{age}` is not a number followed by s, m, h or d")),
+    };
+    Ok(Duration::from_secs(amount * seconds))
+}
+
+fn parse_version(version: &str) -> core::result::Result<String, String> {
+    version.parse::<MigrationVersion>()?;
+    Ok(version.to_string())
+}
+
+impl Commands {
+    /// Reads the Flyway configuration of the commands connecting to the
+    /// database, it fills in what the arguments leave out.
+    fn load_flyway_config(&mut self) -> Result<()> {
+        match self {
+            Commands::Migrate { args }
+            | Commands::Info { args }
+            | Commands::Validate { args }
+            | Commands::Undo { args }
+            | Commands::Repair { args }
+            | Commands::Squash { args, .. }
+            | Commands::ImportHistory { args, .. }
+            | Commands::ExportHistory { args, .. } => args.load_flyway_config(),
+            Commands::Baseline { connection, .. } | Commands::Wait { connection } => {
+                connection.load_flyway_config()
+            }
+            Commands::New { .. } | Commands::Lint { .. } | Commands::TestDb { .. } => Ok(()),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Commands::Migrate { .. } => "migrate",
+            Commands::Info { .. } => "info",
+            Commands::Validate { .. } => "validate",
+            Commands::Undo { .. } => "undo",
+            Commands::Repair { .. } => "repair",
+            Commands::Baseline { .. } => "baseline",
+            Commands::Squash { .. } => "squash",
+            Commands::New { .. } => "new",
+            Commands::Lint { .. } => "lint",
+            Commands::ImportHistory { .. } => "import-history",
+            Commands::ExportHistory { .. } => "export-history",
+            Commands::Wait { .. } => "wait",
+            Commands::TestDb {
+                command: TestDbCommands::Gc { .. },
+            } => "test-db gc",
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct ConnectArgs {
+    /// Database URL or connection string, defaults to the PG* environment variables
+    #[arg(short = 'u', long, default_value = "", hide_default_value = true)]
+    url: String,
+
+    /// Retry connecting this many times while the database is starting
+    #[arg(long, value_name = "N")]
+    connect_retries: Option<u32>,
+
+    /// Seconds before the first retry, doubled after every attempt
+    #[arg(long, value_name = "SECONDS", default_value_t = 1)]
+    connect_retry_interval: u64,
+
+    /// Seconds to wait for a single connection attempt
+    #[arg(long, value_name = "SECONDS")]
+    connect_timeout: Option<u64>,
+
+    /// Flyway configuration file, flyway.toml or flyway.conf are read from the current directory by default
+    #[arg(long, value_name = "FILE")]
+    config: Option<String>,
+
+    #[arg(skip)]
+    flyway: FlywayConfig,
+}
+
+impl ConnectArgs {
+    fn load_flyway_config(&mut self) -> Result<()> {
+        self.flyway = match &self.config {
+            Some(path) => FlywayConfig::load(path)?,
+            None => FlywayConfig::load_default()?.unwrap_or_default(),
+        };
+        for warning in &self.flyway.warnings {
+            eprintln!("Warning: {warning}");
+        }
+        if self.url.is_empty()
+            && let Some(url) = &self.flyway.url
+        {
+            self.url = url.clone();
+        }
+        Ok(())
+    }
+
+    /// Flyway's history table, `flyway.table` in the first of
+    /// `flyway.schemas` unless given.
+    fn flyway_table(&self, table: Option<String>) -> String {
+        table.unwrap_or_else(|| {
+            self.flyway.qualify(
+                self.flyway
+                    .table
+                    .as_deref()
+                    .unwrap_or(pgmt_core::FLYWAY_HISTORY_TABLE),
+            )
+        })
+    }
+
+    /// `retries` is used when `--connect-retries` is not given, `None`
+    /// retries until the database is reachable.
+    async fn connect(&self, retries: Option<u32>) -> Result<Pool> {
+        pgmt_core::connect_with_options(self.url.clone(), &self.connect_options(retries)).await
+    }
+
+    fn connect_options(&self, retries: Option<u32>) -> ConnectOptions {
+        ConnectOptions {
+            retries: self.connect_retries.or(retries),
+            retry_interval: Duration::from_secs(self.connect_retry_interval),
+            timeout: self.connect_timeout.map(Duration::from_secs),
+            ..ConnectOptions::default()
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct MigratorArgs {
+    #[command(flatten)]
+    connection: ConnectArgs,
+
+    /// Directories containing migrations, or glob patterns like `db/**/migrations`, the flyway.locations by default
+    directories: Vec<String>,
+
+    /// Glob pattern for files in the migration directories to ignore
+    #[arg(short = 'i', long = "ignore", value_name = "PATTERN")]
+    ignore: Vec<String>,
+
+    /// Glob pattern for paths, relative to the migration directory, to exclude
+    #[arg(short = 'e', long = "exclude", value_name = "PATTERN")]
+    exclude: Vec<String>,
+
+    /// Follow symbolic links in the migration directories
+    #[arg(long)]
+    follow_symlinks: bool,
+
+    /// Table keeping track of the applied migrations, `_schema_history` by default
+    #[arg(long)]
+    table: Option<String>,
+
+    /// Only migrate up to and including this version
+    #[arg(long, value_parser = parse_version)]
+    target: Option<String>,
+
+    /// Apply pending migrations older than the latest applied migration
+    #[arg(long)]
+    out_of_order: bool,
+
+    /// How migrations are wrapped in transactions
+    #[arg(long, value_enum, default_value_t = TransactionModeArg::PerMigration)]
+    transaction_mode: TransactionModeArg,
+
+    /// Do not run the beforeMigrate, afterMigrate and other hooks
+    #[arg(long)]
+    no_hooks: bool,
+
+    /// lock_timeout of the migration transactions, like `5s`
+    #[arg(long, value_name = "DURATION")]
+    lock_timeout: Option<String>,
+
+    /// statement_timeout of the migration transactions, like `5min`
+    #[arg(long, value_name = "DURATION")]
+    statement_timeout: Option<String>,
+
+    /// Role the migrations run as
+    #[arg(long)]
+    role: Option<String>,
+
+    /// search_path of the migration transactions, like `app,public`
+    #[arg(long)]
+    search_path: Option<String>,
+
+    /// application_name of the migration transactions
+    #[arg(long)]
+    application_name: Option<String>,
+
+    /// Retry a migration this many times when it exceeds the lock_timeout
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    lock_timeout_retries: u32,
+
+    /// Seconds between the retries of a migration that exceeded the lock_timeout
+    #[arg(long, value_name = "SECONDS", default_value_t = 1)]
+    lock_timeout_retry_interval: u64,
+}
+
+impl MigratorArgs {
+    fn load_flyway_config(&mut self) -> Result<()> {
+        self.connection.load_flyway_config()?;
+        if self.directories.is_empty() {
+            self.directories = self.connection.flyway.locations.clone();
+        }
+        Ok(())
+    }
+
+    fn migrator(&self) -> Result<Migrator> {
+        if self.directories.is_empty() {
+            return Err(ConfigError::new(
+                "No migration directories, pass them as arguments or set flyway.locations",
+            )
+            .into());
+        }
+        let config = &self.connection.flyway;
+        // The environment variables override the configuration file.
+        let mut placeholders = config.placeholders.clone();
+        placeholders.extend(collect_placeholders_from_environment_variable());
+        let migrator = Migrator::new()
+            .locations(self.directories.clone())
+            .discovery(DiscoveryOptions {
+                ignore: self.ignore.clone(),
+                exclude: self.exclude.clone(),
+                follow_symlinks: self.follow_symlinks,
+            })
+            .placeholders(placeholders)
+            .history_table(
+                self.table
+                    .clone()
+                    .unwrap_or_else(|| config.qualify(HISTORY_TABLE)),
+            )
+            .out_of_order(self.out_of_order || config.out_of_order == Some(true))
+            .transaction_mode(self.transaction_mode.into())
+            .hooks(!self.no_hooks)
+            .session_settings(SessionSettings {
+                lock_timeout: self.lock_timeout.clone(),
+                statement_timeout: self.statement_timeout.clone(),
+                role: self.role.clone(),
+                search_path: self
+                    .search_path
+                    .clone()
+                    .or_else(|| (!config.schemas.is_empty()).then(|| config.schemas.join(","))),
+                application_name: self.application_name.clone(),
+            })
+            .lock_timeout_retries(
+                self.lock_timeout_retries,
+                Duration::from_secs(self.lock_timeout_retry_interval),
+            );
+        match &self.target {
+            Some(target) => migrator.target(target),
+            None => Ok(migrator),
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct LintArgs {
+    /// Only check the migrations pending in this database, checks every migration when left out
+    #[arg(short = 'u', long)]
+    url: Option<String>,
+
+    /// Directories containing migrations, or glob patterns like `db/**/migrations`
+    #[arg(required = true)]
+    directories: Vec<String>,
+
+    /// Glob pattern for files in the migration directories to ignore
+    #[arg(short = 'i', long = "ignore", value_name = "PATTERN")]
+    ignore: Vec<String>,
+
+    /// Glob pattern for paths, relative to the migration directory, to exclude
+    #[arg(short = 'e', long = "exclude", value_name = "PATTERN")]
+    exclude: Vec<String>,
+
+    /// Follow symbolic links in the migration directories
+    #[arg(long)]
+    follow_symlinks: bool,
+
+    /// Table keeping track of the applied migrations
+    #[arg(long, default_value = "_schema_history")]
+    table: String,
+
+    /// Only check migrations up to and including this version
+    #[arg(long, value_parser = parse_version)]
+    target: Option<String>,
+
+    /// Skip a rule, like `drop-column`, files skip rules with a `-- pgmt-lint: allow rule` comment
+    #[arg(short = 'a', long, value_name = "RULE")]
+    allow: Vec<String>,
+
+    /// Print the findings as a SARIF log for code scanning tools
+    #[arg(long)]
+    sarif: bool,
+}
+
+impl LintArgs {
+    fn migrator(&self) -> Result<Migrator> {
+        let migrator = Migrator::new()
+            .locations(self.directories.clone())
+            .discovery(DiscoveryOptions {
+                ignore: self.ignore.clone(),
+                exclude: self.exclude.clone(),
+                follow_symlinks: self.follow_symlinks,
+            })
+            .history_table(self.table.clone());
+        match &self.target {
+            Some(target) => migrator.target(target),
+            None => Ok(migrator),
+        }
+    }
+}
+
+/// Migration tools whose history table pgmt can convert.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum HistoryFormat {
+    Flyway,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum TransactionModeArg {
+    PerMigration,
+    All,
+    None,
+}
+
+impl From<TransactionModeArg> for TransactionMode {
+    fn from(mode: TransactionModeArg) -> Self {
+        match mode {
+            TransactionModeArg::PerMigration => TransactionMode::PerMigration,
+            TransactionModeArg::All => TransactionMode::All,
+            TransactionModeArg::None => TransactionMode::None,
+        }
+    }
+}
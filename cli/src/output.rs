@@ -0,0 +1,181 @@
+use clap::ValueEnum;
+use pgmt_core::{
+    AppliedMigration, Error, LINT_RULES, LintReport, MigrationEvent, MigrationObserver,
+    SkippedMigration,
+};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::error::Error as _;
+
+/// Version of the JSON documents printed by the CLI. It is only bumped when a
+/// field is removed or changes meaning, new fields can be added at any time.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// `json` prints a single document once the command is done, `ndjson` prints
+/// one document per line and streams a line per migration as it runs.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Ndjson,
+}
+
+impl OutputFormat {
+    pub fn is_text(self) -> bool {
+        self == OutputFormat::Text
+    }
+
+    /// Prints the result of a command, `status` is `ok` unless the command
+    /// found a problem it reports through its exit code, like `validate`.
+    pub fn result<T: Serialize>(self, command: &str, status: &str, result: &T) {
+        self.print(json!({
+            "schema_version": SCHEMA_VERSION,
+            "command": command,
+            "status": status,
+            "result": result,
+        }));
+    }
+
+    pub fn error(self, command: &str, error: &Error) {
+        self.print(json!({
+            "schema_version": SCHEMA_VERSION,
+            "command": command,
+            "status": "error",
+            "error": error_value(error),
+        }));
+    }
+
+    fn print(self, mut document: Value) {
+        match self {
+            OutputFormat::Text => {}
+            OutputFormat::Json => {
+                println!("{}", serde_json::to_string_pretty(&document).unwrap());
+            }
+            OutputFormat::Ndjson => {
+                document["event"] = json!("result");
+                println!("{document}");
+            }
+        }
+    }
+}
+
+fn error_value(error: &Error) -> Value {
+    let (kind, details) = match error {
+        Error::ConfigError(e) => ("configuration", json!(e)),
+        Error::DiscoveryError(e) => ("discovery", json!(e)),
+        Error::InvalidMigrationFilesError(e) => ("invalid_migration_files", json!(e)),
+        Error::DuplicateVersionError(e) => ("duplicate_version", json!(e)),
+        Error::MissingMigrationError(e) => ("missing_migration", json!(e)),
+        Error::MissingUndoError(e) => ("missing_undo", json!(e)),
+        Error::FailedMigrationError(e) => ("failed_migration", json!(e)),
+        Error::HistoryNotEmptyError(e) => ("history_not_empty", json!(e)),
+        Error::ChecksumMismatchError(e) => ("checksum_mismatch", json!(e)),
+        Error::OutOfOrderError(e) => ("out_of_order", json!(e)),
+        Error::SquashedMigrationsError(e) => ("squashed_migrations", json!(e)),
+        Error::MissingVariableTemplateError(e) => ("missing_placeholder", json!(e)),
+        Error::ConnectionError(e) => ("connection", json!(e)),
+        Error::LockError(e) => ("lock", json!(e)),
+        Error::ExecutionError(e) => ("migration_sql", json!(e)),
+        Error::RustMigrationError(e) => ("rust_migration", json!(e)),
+        Error::HistoryCorruptionError(e) => ("history_corruption", json!(e)),
+        Error::DumpError(e) => ("dump", json!(e)),
+        Error::TokioPostgres(e) => (
+            "postgres",
+            json!({ "code": e.code().map(|code| code.code()) }),
+        ),
+    };
+    let mut causes = vec![];
+    let mut source = error.source();
+    while let Some(cause) = source {
+        causes.push(cause.to_string());
+        source = cause.source();
+    }
+    json!({
+        "kind": kind,
+        "category": error.category(),
+        "message": error.to_string(),
+        "details": details,
+        "causes": causes,
+    })
+}
+
+/// Prints the findings of `lint` as a SARIF 2.1.0 log, the format code
+/// scanning tools like GitHub read.
+pub fn print_sarif(report: &LintReport) {
+    let rules: Vec<Value> = LINT_RULES
+        .iter()
+        .map(|rule| {
+            json!({
+                "id": rule.id,
+                "shortDescription": { "text": rule.description },
+            })
+        })
+        .collect();
+    let results: Vec<Value> = report
+        .findings
+        .iter()
+        .map(|finding| {
+            json!({
+                "ruleId": finding.rule,
+                "level": "warning",
+                "message": { "text": finding.message },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": finding.file_path },
+                        "region": { "startLine": finding.line },
+                    },
+                }],
+            })
+        })
+        .collect();
+    let log = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "pgmt",
+                    "informationUri": "https://github.com/agirorn/pgmt",
+                    "rules": rules,
+                },
+            },
+            "results": results,
+        }],
+    });
+    println!("{}", serde_json::to_string_pretty(&log).unwrap());
+}
+
+/// Streams the progress of `migrate` and `undo` as NDJSON events.
+pub struct NdjsonObserver;
+
+impl NdjsonObserver {
+    fn event(&self, event: &str, migration: Value, error: Option<&Error>) {
+        let mut line = json!({
+            "schema_version": SCHEMA_VERSION,
+            "event": event,
+            "migration": migration,
+        });
+        if let Some(error) = error {
+            line["error"] = error_value(error);
+        }
+        println!("{line}");
+    }
+}
+
+impl MigrationObserver for NdjsonObserver {
+    fn started(&self, migration: &MigrationEvent) {
+        self.event("started", json!(migration), None);
+    }
+
+    fn applied(&self, migration: &AppliedMigration) {
+        self.event("applied", json!(migration), None);
+    }
+
+    fn failed(&self, migration: &MigrationEvent, error: &Error) {
+        self.event("failed", json!(migration), Some(error));
+    }
+
+    fn skipped(&self, migration: &SkippedMigration) {
+        self.event("skipped", json!(migration), None);
+    }
+}
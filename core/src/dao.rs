@@ -1,11 +1,34 @@
+use crate::error::Result;
 use chrono::{DateTime, Utc};
 use deadpool_postgres::Client;
 use serde::Serialize;
 use std::convert::TryFrom;
 use tokio_postgres::Row;
+use tokio_postgres::types::ToSql;
 
-pub async fn get_schema_history_rows(client: &Client) -> Vec<SchemaHistoryRow> {
-    let sql = r#"
+/// The default name of the table keeping track of the applied migrations.
+pub const DEFAULT_HISTORY_TABLE: &str = "_schema_history";
+
+/// Quotes a possibly schema qualified table name like `flyway.history` so it
+/// can be used in SQL.
+pub fn quote_table_name(name: &str) -> String {
+    name.split('.')
+        .map(quote_identifier)
+        .collect::<Vec<String>>()
+        .join(".")
+}
+
+/// Quotes a single identifier, dots included, so it can be used in SQL.
+pub(crate) fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+pub async fn get_schema_history_rows(
+    client: &Client,
+    table: &str,
+) -> Result<Vec<SchemaHistoryRow>> {
+    let sql = format!(
+        r#"
         SELECT installed_rank
              , version
              , description
@@ -13,24 +36,240 @@ pub async fn get_schema_history_rows(client: &Client) -> Vec<SchemaHistoryRow> {
              , script
              , checksum
              , installed_by
-             , installed_on
+             -- Flyway's history table has a timestamp without time zone.
+             , installed_on::timestamptz AS installed_on
              , execution_time
              , success
-          FROM _schema_history
+          FROM {}
          ORDER BY installed_rank;
-    "#;
-    if let Ok(rows) = client.query(sql, &[]).await {
-        let rows: Vec<SchemaHistoryRow> = rows
-            .into_iter()
-            .map(SchemaHistoryRow::try_from)
-            .collect::<Result<_, _>>()
-            .unwrap();
-        return rows;
+    "#,
+        quote_table_name(table)
+    );
+    let rows = client
+        .query(&sql, &[])
+        .await?
+        .into_iter()
+        .map(SchemaHistoryRow::try_from)
+        .collect::<std::result::Result<_, _>>()?;
+    Ok(rows)
+}
+
+/// A row to be inserted into the history table.
+pub struct NewSchemaHistoryRow<'a> {
+    pub version: Option<&'a str>,
+    pub description: &'a str,
+    pub r#type: &'a str,
+    pub script: &'a str,
+    pub checksum: Option<i32>,
+    pub execution_time: i32,
+    pub success: bool,
+}
+
+pub async fn insert_schema_history_row(
+    client: &Client,
+    table: &str,
+    row: NewSchemaHistoryRow<'_>,
+) -> Result<()> {
+    let sql = format!(
+        r#"
+           insert into {}
+                ( version
+                , description
+                , type
+                , script
+                , checksum
+                , installed_by
+                , installed_on
+                , execution_time
+                , success
+                )
+           VALUES
+                (  $1 -- version
+                ,  $2 -- description
+                ,  $3 -- type
+                ,  $4 -- script
+                ,  $5 -- checksum
+                ,  $6 -- installed_by
+                ,  $7 -- installed_on
+                ,  $8 -- execution_time
+                ,  $9 -- success
+              )
+        "#,
+        quote_table_name(table)
+    );
+
+    client
+        .execute(
+            &sql,
+            to_sql_params![
+                row.version,        // version
+                row.description,    // description
+                row.r#type,         // type
+                row.script,         // script
+                row.checksum,       // checksum
+                "installed_by",     // installed_by
+                Utc::now(),         // installed_on
+                row.execution_time, // execution_time
+                row.success,        // success
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Inserts rows copied from another history table, keeping their rank, who
+/// installed them and when.
+pub async fn insert_history_rows(
+    client: &Client,
+    table: &str,
+    rows: &[SchemaHistoryRow],
+) -> Result<()> {
+    let quoted = quote_table_name(table);
+    let sql = format!(
+        r#"
+           insert into {quoted}
+                ( installed_rank
+                , version
+                , description
+                , type
+                , script
+                , checksum
+                , installed_by
+                , installed_on
+                , execution_time
+                , success
+                )
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8::timestamptz, $9, $10)
+        "#
+    );
+    let statement = client.prepare(&sql).await?;
+    for row in rows {
+        client
+            .execute(
+                &statement,
+                to_sql_params![
+                    row.installed_rank,
+                    row.version,
+                    row.description,
+                    row.r#type,
+                    row.script,
+                    row.checksum,
+                    row.installed_by,
+                    row.installed_on,
+                    row.execution_time,
+                    row.success,
+                ],
+            )
+            .await?;
+    }
+    // The rows after the copied ones are ranked by the sequence of pgmt's
+    // table, Flyway's table has none.
+    client
+        .execute(
+            &format!(
+                "SELECT setval(sequence::regclass, (SELECT max(installed_rank) FROM {quoted}))
+                   FROM pg_get_serial_sequence($1, 'installed_rank') AS sequence
+                  WHERE sequence IS NOT NULL"
+            ),
+            &[&quoted],
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn create_schema_history_if_needed(client: &Client, table: &str) -> Result<()> {
+    if !schema_history_exists(client, table).await? {
+        create_schema_history_table(client, table).await?;
     }
-    vec![]
+    Ok(())
+}
+
+pub async fn schema_history_exists(client: &Client, table: &str) -> Result<bool> {
+    let sql = "SELECT to_regclass($1) IS NOT NULL AS exists;";
+    let exists: bool = client
+        .query_one(sql, &[&quote_table_name(table)])
+        .await?
+        .try_get("exists")?;
+    Ok(exists)
+}
+
+async fn create_schema_history_table(client: &Client, table: &str) -> Result<()> {
+    let table = quote_table_name(table);
+    client
+        .batch_execute(&format!(
+            r#"
+              CREATE TABLE {table} (
+                    -- Auto-incrementing rank (used as primary key and order of migration)
+                    installed_rank SERIAL PRIMARY KEY,
+
+                    -- Version of the migration (e.g., 1.2, 2.0)
+                    version VARCHAR(50),
+
+                    -- Human-readable description (e.g., Create users table)
+                    description VARCHAR(200) NOT NULL,
+
+                    -- Type of migration (SQL, JDBC, UNDO, etc.)
+                    type VARCHAR(20) NOT NULL,
+
+                    -- Filename of the migration script
+                    script VARCHAR(1000) NOT NULL,
+
+                    -- Checksum used to detect script changes
+                    checksum INT,
+
+                    -- Database user who ran the migration
+                    installed_by VARCHAR(100) NOT NULL,
+
+                    -- Timestamp when the migration was applied
+                    installed_on TIMESTAMPTZ NOT NULL DEFAULT now(),
+
+                    -- Time in milliseconds to execute the migration
+                    execution_time INT NOT NULL,
+
+                    -- Whether the migration succeeded (true) or failed (false)
+                    success BOOLEAN NOT NULL
+              );
+
+
+              COMMENT ON COLUMN {table}.installed_rank
+                   IS 'Execution order rank (primary key); increments with each migration';
+
+              COMMENT ON COLUMN {table}.version
+                   IS 'Version of the migration (e.g., 1.0, 2.1.3). Null for repeatable migrations';
+
+              COMMENT ON COLUMN {table}.description
+                   IS 'Human-readable description of the migration (e.g., Create users table)';
+
+              COMMENT ON COLUMN {table}.type
+                   IS 'Type of migration (e.g., SQL, JDBC, REPEATABLE, UNDO)';
+
+              COMMENT ON COLUMN {table}.script
+                   IS 'Name of the migration script file';
+
+              COMMENT ON COLUMN {table}.checksum
+                   IS 'Checksum of the migration script content to detect changes. Null for repeatable if not validated';
+
+              COMMENT ON COLUMN {table}.installed_by
+                   IS 'Database user who applied the migration';
+
+              COMMENT ON COLUMN {table}.installed_on
+                   IS 'Timestamp when the migration was applied. Defaults to current time';
+
+              COMMENT ON COLUMN {table}.execution_time
+                   IS 'Execution time of the migration in milliseconds';
+
+              COMMENT ON COLUMN {table}.success
+                   IS 'Whether the migration was successful (true) or failed (false)';
+
+
+            "#
+        ))
+        .await?;
+
+    Ok(())
 }
 
-#[derive(Debug, Serialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, PartialEq)]
 pub struct SchemaHistoryRow {
     /// Auto-incrementing rank (used as primary key and order of migration)
     pub installed_rank: i32,
@@ -48,7 +287,7 @@ pub struct SchemaHistoryRow {
     pub script: String,
 
     /// Checksum used to detect script changes
-    pub checksum: i32,
+    pub checksum: Option<i32>,
 
     /// Database user who ran the migration
     pub installed_by: String,
@@ -67,7 +306,7 @@ impl TryFrom<Row> for SchemaHistoryRow {
     type Error = tokio_postgres::Error;
 
     /// Try to convert from Row into TableColumn
-    fn try_from(row: Row) -> Result<Self, Self::Error> {
+    fn try_from(row: Row) -> std::result::Result<Self, Self::Error> {
         Ok(SchemaHistoryRow {
             installed_rank: row.try_get("installed_rank")?,
             version: row.try_get("version")?,
@@ -0,0 +1,400 @@
+use crate::error::{
+    ConfigError, DiscoveryError, Error, InvalidMigrationFile, InvalidMigrationFilesError, Result,
+};
+use crate::{MigrationVersion, SqlFile};
+use glob::Pattern;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Names (without the `.sql` extension) of the Flyway style callback scripts
+/// that are allowed to live next to the migrations.
+pub const HOOKS: &[&str] = &[
+    "beforeMigrate",
+    "beforeEachMigrate",
+    "afterEachMigrate",
+    "afterMigrate",
+    "afterMigrateError",
+];
+
+/// Flyway callbacks pgmt does not run, reported instead of ignored.
+const UNSUPPORTED_HOOKS: &[&str] = &[
+    "beforeEachMigrateStatement",
+    "afterEachMigrateStatement",
+    "afterEachMigrateStatementError",
+    "afterEachMigrateError",
+    "afterMigrateApplied",
+    "afterVersioned",
+];
+
+/// Options controlling how the migration locations are scanned.
+#[derive(Debug, Clone, Default)]
+pub struct DiscoveryOptions {
+    /// Glob patterns matched against file names, matching files are skipped.
+    pub ignore: Vec<String>,
+    /// Glob patterns matched against the path relative to the location,
+    /// matching files and directories are skipped (e.g. `drafts/**`).
+    pub exclude: Vec<String>,
+    /// Follow symbolic links instead of reporting them as invalid files.
+    pub follow_symlinks: bool,
+}
+
+/// Recursively reads every file in the migration locations and fails with a
+/// single error listing all the files that are neither a migration, a hook
+/// nor matched by one of the `ignore` or `exclude` patterns.
+///
+/// A location is either a directory or a glob pattern like `db/**/migrations`
+/// matching one or more directories. Files are returned in a deterministic
+/// order, location by location, sorted by path.
+pub fn read_sql_files<P>(locations: Vec<P>, options: &DiscoveryOptions) -> Result<Vec<SqlFile>>
+where
+    P: Into<String>,
+{
+    let ignore = compile_patterns("ignore", &options.ignore)?;
+    let exclude = compile_patterns("exclude", &options.exclude)?;
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+    let mut files = Vec::new();
+    let mut invalid = Vec::new();
+
+    for location in locations {
+        for dir in expand_location(&location.into())? {
+            let walker = WalkDir::new(&dir)
+                .follow_links(options.follow_symlinks)
+                .sort_by_file_name()
+                .into_iter()
+                .filter_entry(|entry| {
+                    entry.depth() == 0 || !is_excluded(&exclude, &dir, entry.path())
+                });
+
+            for entry in walker {
+                let entry = entry.map_err(|e| {
+                    let path = e.path().unwrap_or(&dir).to_path_buf();
+                    read_error(&path, e)
+                })?;
+                let file_path = entry.path();
+
+                if entry.depth() > 0 && entry.path_is_symlink() && !options.follow_symlinks {
+                    invalid.push(InvalidMigrationFile {
+                        file_path: file_path.to_string_lossy().to_string(),
+                        reason: "symbolic link, enable following symlinks to use it".to_string(),
+                    });
+                    continue;
+                }
+
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                if !entry.file_type().is_file() || ignore.iter().any(|p| p.matches(&file_name)) {
+                    continue;
+                }
+
+                // The same file can be reached twice through overlapping
+                // locations or symlinks, it should only be migrated once.
+                let canonical =
+                    fs::canonicalize(file_path).map_err(|e| read_error(file_path, e))?;
+                if !seen.insert(canonical) {
+                    continue;
+                }
+
+                if let Err(reason) = validate_file_name(&file_name) {
+                    invalid.push(InvalidMigrationFile {
+                        file_path: file_path.to_string_lossy().to_string(),
+                        reason,
+                    });
+                    continue;
+                }
+
+                let content =
+                    fs::read_to_string(file_path).map_err(|e| read_error(file_path, e))?;
+                files.push(SqlFile {
+                    content,
+                    file_name,
+                    file_path: file_path.to_string_lossy().to_string(),
+                });
+            }
+        }
+    }
+
+    if !invalid.is_empty() {
+        invalid.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+        return Err(InvalidMigrationFilesError { files: invalid }.into());
+    }
+
+    Ok(files)
+}
+
+/// Expands a location into the directories it points to, sorted by path.
+fn expand_location(location: &str) -> Result<Vec<PathBuf>> {
+    if !location.contains(['*', '?', '[']) {
+        let path = PathBuf::from(location);
+        if !path.is_dir() {
+            return Err(DiscoveryError {
+                path: location.to_string(),
+                message: "migration location is not a directory".to_string(),
+                source: None,
+            }
+            .into());
+        }
+        return Ok(vec![path]);
+    }
+
+    let paths = glob::glob(location).map_err(|e| ConfigError {
+        message: format!("Invalid location pattern `{location}`: {e}"),
+        source: Some(Box::new(e)),
+    })?;
+    let mut dirs = Vec::new();
+    for path in paths {
+        let path = path.map_err(|e| {
+            let path = e.path().to_path_buf();
+            read_error(&path, e)
+        })?;
+        if path.is_dir() {
+            dirs.push(path);
+        }
+    }
+    if dirs.is_empty() {
+        return Err(DiscoveryError {
+            path: location.to_string(),
+            message: "migration location does not match any directory".to_string(),
+            source: None,
+        }
+        .into());
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+fn is_excluded(exclude: &[Pattern], root: &Path, path: &Path) -> bool {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let relative = relative.to_string_lossy().replace('\\', "/");
+    exclude.iter().any(|p| p.matches(&relative))
+}
+
+fn compile_patterns(kind: &str, patterns: &[String]) -> Result<Vec<Pattern>> {
+    patterns
+        .iter()
+        .map(|p| {
+            Pattern::new(p).map_err(|e| {
+                ConfigError {
+                    message: format!("Invalid {kind} pattern `{p}`: {e}"),
+                    source: Some(Box::new(e)),
+                }
+                .into()
+            })
+        })
+        .collect()
+}
+
+fn read_error<E>(path: &Path, error: E) -> Error
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    DiscoveryError {
+        path: path.to_string_lossy().to_string(),
+        message: error.to_string(),
+        source: Some(Box::new(error)),
+    }
+    .into()
+}
+
+/// Returns true if the file name is one of the supported hooks.
+pub fn is_hook(file_name: &str) -> bool {
+    file_name
+        .strip_suffix(".sql")
+        .is_some_and(|name| HOOKS.contains(&name))
+}
+
+/// Checks that a file name is something pgmt can use and returns the reason
+/// when it is not.
+pub fn validate_file_name(file_name: &str) -> std::result::Result<(), String> {
+    let Some(stem) = file_name.strip_suffix(".sql") else {
+        return Err("not a .sql file".to_string());
+    };
+
+    if HOOKS.contains(&stem) {
+        return Ok(());
+    }
+    if UNSUPPORTED_HOOKS.contains(&stem) {
+        return Err(format!(
+            "the `{stem}` callback is not supported, expected one of {}",
+            HOOKS.join(", ")
+        ));
+    }
+
+    let mut chars = stem.chars();
+    let Some(prefix) = chars.next() else {
+        return Err("empty file name".to_string());
+    };
+    let rest = chars.as_str();
+    if prefix == 'R' {
+        return Err("repeatable migrations are not supported".to_string());
+    }
+    if !matches!(prefix, 'V' | 'U' | 'B') {
+        return Err(format!(
+            "unsupported prefix `{prefix}`, expected one of V, U, B or a hook name"
+        ));
+    }
+    let Some((version, description)) = rest.split_once("__") else {
+        return Err(format!(
+            "missing `__` separator between version and description, expected {prefix}<version>__<description>.sql"
+        ));
+    };
+    if description.is_empty() {
+        return Err("missing description after `__`".to_string());
+    }
+
+    if version.is_empty() {
+        return Err(format!("missing version after the `{prefix}` prefix"));
+    }
+    version.parse::<MigrationVersion>().map(|_| ())
+}
+
+#[test]
+fn test_validate_file_name() {
+    use pretty_assertions::assert_eq;
+    assert_eq!(validate_file_name("V1.0.0__Create_table.sql"), Ok(()));
+    assert_eq!(validate_file_name("U1.0.0__Drop_table.sql"), Ok(()));
+    assert_eq!(
+        validate_file_name("R__Views.sql"),
+        Err("repeatable migrations are not supported".to_string())
+    );
+    assert_eq!(
+        validate_file_name("afterVersioned.sql"),
+        Err("the `afterVersioned` callback is not supported, expected one of beforeMigrate, beforeEachMigrate, afterEachMigrate, afterMigrate, afterMigrateError".to_string())
+    );
+    assert_eq!(validate_file_name("B2.0__Squashed.sql"), Ok(()));
+    assert_eq!(validate_file_name("beforeMigrate.sql"), Ok(()));
+    assert_eq!(
+        validate_file_name("README.md"),
+        Err("not a .sql file".to_string())
+    );
+    assert_eq!(
+        validate_file_name("X1.0.0__Create_table.sql"),
+        Err("unsupported prefix `X`, expected one of V, U, B or a hook name".to_string())
+    );
+    assert_eq!(
+        validate_file_name("V1.0.0_Create_table.sql"),
+        Err("missing `__` separator between version and description, expected V<version>__<description>.sql".to_string())
+    );
+    assert_eq!(validate_file_name("V1__Create_table.sql"), Ok(()));
+    assert_eq!(validate_file_name("V2_1__Create_table.sql"), Ok(()));
+    assert_eq!(validate_file_name("V20240105__Create_table.sql"), Ok(()));
+    assert_eq!(
+        validate_file_name("V1.0.0-beta__Create_table.sql"),
+        Err("version `1.0.0-beta` has a non numeric part `0-beta`".to_string())
+    );
+    assert!(validate_file_name("R1__Views.sql").is_err());
+    for hook in UNSUPPORTED_HOOKS {
+        assert!(validate_file_name(&format!("{hook}.sql")).is_err());
+    }
+}
+
+#[test]
+fn test_read_sql_files_reports_every_invalid_file() {
+    use crate::vec_of_string;
+    use pretty_assertions::assert_eq;
+    let res = read_sql_files(
+        vec!["tests/invalid_migrations"],
+        &DiscoveryOptions::default(),
+    );
+    let Err(Error::InvalidMigrationFilesError(error)) = res else {
+        panic!("Expected InvalidMigrationFilesError, got {res:?}");
+    };
+    let names: Vec<&str> = error
+        .files
+        .iter()
+        .map(|f| f.file_path.rsplit('/').next().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        vec!["README.md", "V1.x__Bad_version.sql", "create_table.sql"]
+    );
+
+    let options = DiscoveryOptions {
+        ignore: vec_of_string!["*.md", "V1.x__*", "create_*"],
+        ..Default::default()
+    };
+    let files = read_sql_files(vec!["tests/invalid_migrations"], &options).unwrap();
+    let mut names: Vec<String> = files.into_iter().map(|f| f.file_name).collect();
+    names.sort();
+    assert_eq!(names, vec!["V1.0.0__Valid.sql", "beforeMigrate.sql"]);
+}
+
+#[test]
+fn test_read_sql_files_recursively() {
+    use crate::vec_of_string;
+    use pretty_assertions::assert_eq;
+    let options = DiscoveryOptions {
+        exclude: vec_of_string!["drafts"],
+        ..Default::default()
+    };
+    let files = read_sql_files(vec!["tests/nested_migrations"], &options).unwrap();
+    let paths: Vec<String> = files.into_iter().map(|f| f.file_path).collect();
+    assert_eq!(
+        paths,
+        vec_of_string![
+            "tests/nested_migrations/2024/q3/V1.0.0__Create_table_1_name.sql",
+            "tests/nested_migrations/2024/q4/V1.0.1__Add_table_2_name.sql",
+        ]
+    );
+
+    let res = read_sql_files(
+        vec!["tests/nested_migrations"],
+        &DiscoveryOptions::default(),
+    );
+    assert!(matches!(res, Err(Error::InvalidMigrationFilesError(_))));
+}
+
+#[test]
+fn test_read_sql_files_with_glob_locations() {
+    use crate::vec_of_string;
+    use pretty_assertions::assert_eq;
+    let files = read_sql_files(
+        vec!["tests/nested_migrations/2024/*"],
+        &DiscoveryOptions::default(),
+    )
+    .unwrap();
+    let names: Vec<String> = files.into_iter().map(|f| f.file_name).collect();
+    assert_eq!(
+        names,
+        vec_of_string![
+            "V1.0.0__Create_table_1_name.sql",
+            "V1.0.1__Add_table_2_name.sql",
+        ]
+    );
+
+    let res = read_sql_files(vec!["tests/no_such_dir/*"], &DiscoveryOptions::default());
+    assert!(res.is_err());
+    let res = read_sql_files(vec!["tests/no_such_dir"], &DiscoveryOptions::default());
+    assert!(res.is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_read_sql_files_with_symlinks() {
+    use crate::vec_of_string;
+    use pretty_assertions::assert_eq;
+    let dir = std::env::temp_dir().join(format!("pgmt_symlinks_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let target = fs::canonicalize("tests/nested_migrations/2024").unwrap();
+    std::os::unix::fs::symlink(target, dir.join("2024")).unwrap();
+    let location = dir.to_string_lossy().to_string();
+
+    let res = read_sql_files(vec![location.clone()], &DiscoveryOptions::default());
+    assert!(matches!(res, Err(Error::InvalidMigrationFilesError(_))));
+
+    let options = DiscoveryOptions {
+        follow_symlinks: true,
+        ..Default::default()
+    };
+    let files = read_sql_files(vec![location.clone(), location], &options).unwrap();
+    let names: Vec<String> = files.into_iter().map(|f| f.file_name).collect();
+    assert_eq!(
+        names,
+        vec_of_string![
+            "V1.0.0__Create_table_1_name.sql",
+            "V1.0.1__Add_table_2_name.sql",
+        ]
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
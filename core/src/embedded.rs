@@ -0,0 +1,53 @@
+use crate::SqlFile;
+
+/// Migrations compiled into the binary with `pgmt::embed_migrations!`.
+///
+/// ```ignore
+/// static MIGRATIONS: pgmt::EmbeddedMigrations = pgmt::embed_migrations!("migrations");
+///
+/// pgmt::migrate(&pool, MIGRATIONS.files(), placeholders).await?;
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct EmbeddedMigrations {
+    files: &'static [EmbeddedFile],
+}
+
+/// A single migration file embedded in the binary.
+#[derive(Debug, Clone, Copy)]
+pub struct EmbeddedFile {
+    pub file_name: &'static str,
+    pub file_path: &'static str,
+    pub content: &'static str,
+}
+
+impl EmbeddedMigrations {
+    pub const fn new(files: &'static [EmbeddedFile]) -> Self {
+        Self { files }
+    }
+
+    /// The embedded files as they were found on disk at compile time.
+    pub fn embedded_files(&self) -> &'static [EmbeddedFile] {
+        self.files
+    }
+
+    /// The embedded files, ready to be passed to `migrate`.
+    pub fn files(&self) -> Vec<SqlFile> {
+        self.files.iter().map(SqlFile::from).collect()
+    }
+}
+
+impl From<&EmbeddedFile> for SqlFile {
+    fn from(file: &EmbeddedFile) -> Self {
+        SqlFile {
+            content: file.content.to_string(),
+            file_name: file.file_name.to_string(),
+            file_path: file.file_path.to_string(),
+        }
+    }
+}
+
+impl From<EmbeddedMigrations> for Vec<SqlFile> {
+    fn from(migrations: EmbeddedMigrations) -> Self {
+        migrations.files()
+    }
+}
@@ -1,48 +1,477 @@
 use derive_more::From;
+use serde::Serialize;
 
 pub type Result<T> = core::result::Result<T, Error>;
 
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
 #[allow(clippy::enum_variant_names)]
 #[derive(Debug, From)]
 pub enum Error {
-    // // -- String errors
     #[from]
-    Message(String),
+    ConfigError(ConfigError),
     #[from]
-    IoError(std::io::Error),
+    DiscoveryError(DiscoveryError),
     #[from]
-    TokioPostgres(tokio_postgres::Error),
+    InvalidMigrationFilesError(InvalidMigrationFilesError),
+    #[from]
+    DuplicateVersionError(DuplicateVersionError),
+    #[from]
+    MissingMigrationError(MissingMigrationError),
+    #[from]
+    MissingUndoError(MissingUndoError),
+    #[from]
+    FailedMigrationError(FailedMigrationError),
+    #[from]
+    HistoryNotEmptyError(HistoryNotEmptyError),
     #[from]
     ChecksumMismatchError(ChecksumMismatchError),
     #[from]
+    OutOfOrderError(OutOfOrderError),
+    #[from]
+    SquashedMigrationsError(SquashedMigrationsError),
+    #[from]
     MissingVariableTemplateError(MissingVariableTemplateError),
+    #[from]
+    ConnectionError(ConnectionError),
+    #[from]
+    LockError(LockError),
+    ExecutionError(Box<ExecutionError>),
+    #[from]
+    RustMigrationError(RustMigrationError),
+    #[from]
+    HistoryCorruptionError(HistoryCorruptionError),
+    #[from]
+    DumpError(DumpError),
+    /// Any other database error, like a failed query on the history table.
+    #[from]
+    TokioPostgres(tokio_postgres::Error),
+}
+
+/// What went wrong, broadly, for callers that do not care about the exact
+/// variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    /// Invalid options, versions, patterns or database URLs.
+    Config,
+    /// The migration locations could not be read or contain invalid files.
+    Discovery,
+    /// The migration files do not match the history table.
+    Validation,
+    Checksum,
+    Ordering,
+    Template,
+    Connection,
+    Lock,
+    /// The SQL of a migration or hook, or a Rust migration, failed.
+    Execution,
+    /// The history table has rows pgmt does not understand.
+    HistoryCorruption,
+    Database,
+}
+
+impl Error {
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            Error::ConfigError(_) => ErrorCategory::Config,
+            Error::DiscoveryError(_)
+            | Error::InvalidMigrationFilesError(_)
+            | Error::DuplicateVersionError(_) => ErrorCategory::Discovery,
+            Error::MissingMigrationError(_)
+            | Error::MissingUndoError(_)
+            | Error::FailedMigrationError(_)
+            | Error::HistoryNotEmptyError(_)
+            | Error::SquashedMigrationsError(_) => ErrorCategory::Validation,
+            Error::ChecksumMismatchError(_) => ErrorCategory::Checksum,
+            Error::OutOfOrderError(_) => ErrorCategory::Ordering,
+            Error::MissingVariableTemplateError(_) => ErrorCategory::Template,
+            Error::ConnectionError(_) => ErrorCategory::Connection,
+            Error::LockError(_) => ErrorCategory::Lock,
+            Error::ExecutionError(_) | Error::RustMigrationError(_) => ErrorCategory::Execution,
+            Error::HistoryCorruptionError(_) => ErrorCategory::HistoryCorruption,
+            Error::TokioPostgres(_) | Error::DumpError(_) => ErrorCategory::Database,
+        }
+    }
+
+    /// The SQLSTATE code reported by the database, like `42P01` for an
+    /// undefined table.
+    pub fn sql_state(&self) -> Option<&str> {
+        match self {
+            Error::ExecutionError(e) => e.code.as_deref(),
+            Error::LockError(e) => e.source.code().map(|code| code.code()),
+            Error::TokioPostgres(e) => e.code().map(|code| code.code()),
+            _ => None,
+        }
+    }
+}
+
+/// An invalid option, like a version that can not be parsed or a database
+/// URL that can not be used.
+#[derive(Debug, Serialize)]
+pub struct ConfigError {
+    pub message: String,
+    #[serde(skip)]
+    pub source: Option<BoxError>,
 }
 
-#[derive(Debug)]
+impl ConfigError {
+    pub fn new<M: Into<String>>(message: M) -> Self {
+        Self {
+            message: message.into(),
+            source: None,
+        }
+    }
+}
+
+/// A migration location that does not exist or can not be read.
+#[derive(Debug, Serialize)]
+pub struct DiscoveryError {
+    pub path: String,
+    pub message: String,
+    #[serde(skip)]
+    pub source: Option<BoxError>,
+}
+
+#[derive(Debug, Serialize)]
 pub struct ChecksumMismatchError {
     pub file_name: String,
     pub file_checksum: i32,
     pub applied_checksum: i32,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct MissingVariableTemplateError {
     pub name: String,
 }
 
+/// Every file found in the migration directories that is not a valid
+/// migration, hook or ignored file.
+#[derive(Debug, Serialize)]
+pub struct InvalidMigrationFilesError {
+    pub files: Vec<InvalidMigrationFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InvalidMigrationFile {
+    pub file_path: String,
+    pub reason: String,
+}
+
+/// Two or more migrations of the same type share a version, typically because
+/// two migration directories contain the same version.
+#[derive(Debug, Serialize)]
+pub struct DuplicateVersionError {
+    pub prefix: String,
+    pub version: String,
+    pub file_paths: Vec<String>,
+}
+
+/// A migration recorded as applied in the history table has no file.
+#[derive(Debug, Serialize)]
+pub struct MissingMigrationError {
+    pub version: String,
+    pub script: String,
+}
+
+/// An applied migration has to be undone but there is no undo migration for
+/// its version.
+#[derive(Debug, Serialize)]
+pub struct MissingUndoError {
+    pub version: String,
+    pub script: String,
+}
+
+/// A pending migration has a lower version than the latest applied migration
+/// and out of order migrations are not allowed.
+#[derive(Debug, Serialize)]
+pub struct OutOfOrderError {
+    pub version: String,
+    pub script: String,
+    pub latest_version: String,
+}
+
+/// The database is behind the latest baseline migration and the migrations
+/// it squashes are gone, they must be restored to migrate it.
+#[derive(Debug, Serialize)]
+pub struct SquashedMigrationsError {
+    /// The latest version applied to the database.
+    pub version: String,
+    pub baseline: String,
+}
+
+/// The history table has a failed migration that must be fixed with `repair`
+/// before migrating again.
+#[derive(Debug, Serialize)]
+pub struct FailedMigrationError {
+    pub version: Option<String>,
+    pub script: String,
+}
+
+/// Only an empty history table can be baselined, or have another history
+/// copied into it.
+#[derive(Debug, Serialize)]
+pub struct HistoryNotEmptyError {
+    pub table: String,
+    /// What was refused, like `baseline`.
+    pub operation: String,
+}
+
+/// Unable to get a connection to the database.
+#[derive(Debug, Serialize)]
+pub struct ConnectionError {
+    pub message: String,
+    #[serde(skip)]
+    pub source: Option<BoxError>,
+}
+
+/// Unable to take the advisory lock that keeps two migrations from running at
+/// the same time.
+#[derive(Debug, Serialize)]
+pub struct LockError {
+    pub key: i64,
+    #[serde(skip)]
+    pub source: tokio_postgres::Error,
+}
+
+/// The SQL of a migration or hook failed.
+#[derive(Debug, Serialize)]
+pub struct ExecutionError {
+    pub script: String,
+    pub version: Option<String>,
+    /// The SQLSTATE code, missing when the error did not come from the
+    /// database, like a lost connection.
+    pub code: Option<String>,
+    pub message: String,
+    /// Character position of the error in the SQL, starting at 1.
+    pub position: Option<u32>,
+    pub detail: Option<String>,
+    pub hint: Option<String>,
+    #[serde(skip)]
+    pub source: tokio_postgres::Error,
+}
+
+impl ExecutionError {
+    pub fn new(script: String, version: Option<String>, source: tokio_postgres::Error) -> Self {
+        let db = source.as_db_error();
+        Self {
+            script,
+            version,
+            code: db.map(|e| e.code().code().to_string()),
+            message: db.map_or_else(|| source.to_string(), |e| e.message().to_string()),
+            position: db.and_then(|e| match e.position()? {
+                tokio_postgres::error::ErrorPosition::Original(position) => Some(*position),
+                tokio_postgres::error::ErrorPosition::Internal { .. } => None,
+            }),
+            detail: db.and_then(|e| e.detail()).map(str::to_string),
+            hint: db.and_then(|e| e.hint()).map(str::to_string),
+            source,
+        }
+    }
+}
+
+/// A Rust migration failed with an error that did not come from the
+/// database.
+#[derive(Debug, Serialize)]
+pub struct RustMigrationError {
+    pub script: String,
+    pub version: Option<String>,
+    pub message: String,
+    #[serde(skip)]
+    pub source: BoxError,
+}
+
+/// A row in the history table that pgmt does not understand, typically
+/// because it was edited by hand.
+#[derive(Debug, Serialize)]
+pub struct HistoryCorruptionError {
+    pub table: String,
+    pub installed_rank: i32,
+    pub reason: String,
+}
+
+/// `pg_dump` could not dump the schema of a squashed database.
+#[derive(Debug, Serialize)]
+pub struct DumpError {
+    pub message: String,
+    #[serde(skip)]
+    pub source: Option<BoxError>,
+}
+
 // region:    --- Error Boilerplate
 impl core::fmt::Display for Error {
     fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::result::Result<(), core::fmt::Error> {
-        write!(fmt, "{self:?}")
+        match self {
+            Error::ConfigError(e) => write!(fmt, "{}", e.message),
+            Error::DiscoveryError(e) => write!(fmt, "{}: {}", e.path, e.message),
+            Error::InvalidMigrationFilesError(e) => write!(fmt, "{e}"),
+            Error::DuplicateVersionError(e) => write!(fmt, "{e}"),
+            Error::MissingMigrationError(e) => write!(fmt, "{e}"),
+            Error::MissingUndoError(e) => write!(
+                fmt,
+                "No undo migration found for version {} ({})",
+                e.version, e.script
+            ),
+            Error::FailedMigrationError(e) => write!(fmt, "{e}"),
+            Error::HistoryNotEmptyError(e) => write!(
+                fmt,
+                "Unable to {}, the history table {} is not empty",
+                e.operation, e.table
+            ),
+            Error::ChecksumMismatchError(e) => write!(fmt, "{e}"),
+            Error::OutOfOrderError(e) => write!(fmt, "{e}"),
+            Error::SquashedMigrationsError(e) => write!(fmt, "{e}"),
+            Error::MissingVariableTemplateError(e) => write!(fmt, "{e}"),
+            Error::ConnectionError(e) => {
+                write!(fmt, "Unable to connect to the database: {}", e.message)
+            }
+            Error::LockError(e) => write!(
+                fmt,
+                "Unable to take the migration lock {}: {}",
+                e.key, e.source
+            ),
+            Error::ExecutionError(e) => write!(fmt, "{e}"),
+            Error::RustMigrationError(e) => {
+                write!(fmt, "Migration {} failed: {}", e.script, e.message)
+            }
+            Error::HistoryCorruptionError(e) => write!(
+                fmt,
+                "The history table {} is corrupt, row {}: {}",
+                e.table, e.installed_rank, e.reason
+            ),
+            Error::TokioPostgres(e) => write!(fmt, "Database error: {e}"),
+            Error::DumpError(e) => write!(fmt, "Unable to dump the schema: {}", e.message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::ConfigError(e) => e.source.as_deref().map(|e| e as _),
+            Error::DiscoveryError(e) => e.source.as_deref().map(|e| e as _),
+            Error::ConnectionError(e) => e.source.as_deref().map(|e| e as _),
+            Error::DumpError(e) => e.source.as_deref().map(|e| e as _),
+            Error::LockError(e) => Some(&e.source),
+            Error::ExecutionError(e) => Some(&e.source),
+            Error::RustMigrationError(e) => Some(e.source.as_ref()),
+            Error::TokioPostgres(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl core::fmt::Display for ChecksumMismatchError {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            fmt,
+            "Checksum mismatch for {}: applied {}, file {}",
+            self.file_name, self.applied_checksum, self.file_checksum
+        )
+    }
+}
+
+impl core::fmt::Display for MissingVariableTemplateError {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(fmt, "Missing value for the placeholder ${{{}}}", self.name)
     }
 }
 
-impl std::error::Error for Error {}
+impl core::fmt::Display for InvalidMigrationFilesError {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(fmt, "Invalid files in the migration directories:")?;
+        for file in &self.files {
+            write!(fmt, "\n  {}: {}", file.file_path, file.reason)?;
+        }
+        Ok(())
+    }
+}
+
+impl core::fmt::Display for DuplicateVersionError {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            fmt,
+            "Version {} of {} migrations is used more than once: {}",
+            self.version,
+            self.prefix,
+            self.file_paths.join(", ")
+        )
+    }
+}
+
+impl core::fmt::Display for MissingMigrationError {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            fmt,
+            "Applied migration {} ({}) is missing",
+            self.version, self.script
+        )
+    }
+}
+
+impl core::fmt::Display for OutOfOrderError {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            fmt,
+            "Migration {} is out of order, the latest applied version is {}",
+            self.script, self.latest_version
+        )
+    }
+}
+
+impl core::fmt::Display for SquashedMigrationsError {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            fmt,
+            "The database is at version {}, before the baseline {}, restore the migrations it squashes to migrate it",
+            self.version, self.baseline
+        )
+    }
+}
+
+impl core::fmt::Display for FailedMigrationError {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(fmt, "Migration {} failed, run repair", self.script)
+    }
+}
+
+impl core::fmt::Display for ExecutionError {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(fmt, "Migration {} failed: {}", self.script, self.message)?;
+        if let Some(code) = &self.code {
+            write!(fmt, " (SQLSTATE {code})")?;
+        }
+        if let Some(detail) = &self.detail {
+            write!(fmt, "\nDETAIL: {detail}")?;
+        }
+        if let Some(hint) = &self.hint {
+            write!(fmt, "\nHINT: {hint}")?;
+        }
+        Ok(())
+    }
+}
 // endregion: --- Error Boilerplate
 
-// converting string to out error when calling .into()
-impl From<&str> for Error {
-    fn from(value: &str) -> Self {
-        Self::Message(value.to_string())
+impl From<ExecutionError> for Error {
+    fn from(error: ExecutionError) -> Self {
+        Self::ExecutionError(Box::new(error))
+    }
+}
+
+impl From<deadpool_postgres::PoolError> for Error {
+    fn from(error: deadpool_postgres::PoolError) -> Self {
+        Self::ConnectionError(ConnectionError {
+            message: error.to_string(),
+            source: Some(Box::new(error)),
+        })
+    }
+}
+
+impl From<deadpool_postgres::BuildError> for Error {
+    fn from(error: deadpool_postgres::BuildError) -> Self {
+        Self::ConfigError(ConfigError {
+            message: format!("Invalid database configuration: {error}"),
+            source: Some(Box::new(error)),
+        })
     }
 }
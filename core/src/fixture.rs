@@ -0,0 +1,134 @@
+use crate::dao::{quote_identifier, quote_table_name};
+use crate::error::{ConfigError, ExecutionError, Result};
+use crate::template::fill_template;
+use crate::{Placeholders, Pool};
+use futures_util::SinkExt;
+use std::path::Path;
+
+/// Extensions of the fixture files, SQL run like a migration or CSV copied
+/// into the table named like the file.
+pub const FIXTURE_EXTENSIONS: [&str; 2] = ["sql", "csv"];
+
+/// Reference data loaded into a test database after the migrations, not
+/// recorded in the history table.
+#[derive(Debug, Clone)]
+pub(crate) struct Fixture {
+    pub(crate) path: String,
+    pub(crate) content: String,
+}
+
+impl Fixture {
+    pub(crate) fn read(path: &Path) -> Result<Self> {
+        let extension = path.extension().and_then(|e| e.to_str());
+        let path = path.to_string_lossy();
+        if !extension.is_some_and(|e| FIXTURE_EXTENSIONS.contains(&e)) {
+            return Err(ConfigError::new(format!(
+                "Unable to load the fixture {path}, fixtures are .sql or .csv files"
+            ))
+            .into());
+        }
+        let content = std::fs::read_to_string(&*path).map_err(|e| ConfigError {
+            message: format!("Unable to read the fixture {path}: {e}"),
+            source: Some(Box::new(e)),
+        })?;
+        Ok(Fixture {
+            path: path.to_string(),
+            content,
+        })
+    }
+
+    fn is_csv(&self) -> bool {
+        self.path.ends_with(".csv")
+    }
+
+    /// `COPY` into the table named like the file, like `app.users.csv`, the
+    /// columns are the header of the file.
+    fn copy_statement(&self) -> Result<String> {
+        let table = Path::new(&self.path)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default();
+        let columns = csv_header(&self.content);
+        if columns.iter().all(String::is_empty) {
+            return Err(ConfigError::new(format!(
+                "The fixture {} has no header with the columns",
+                self.path
+            ))
+            .into());
+        }
+        let columns: Vec<String> = columns
+            .iter()
+            .map(|column| quote_identifier(column))
+            .collect();
+        Ok(format!(
+            "COPY {} ({}) FROM STDIN WITH (FORMAT csv, HEADER true)",
+            quote_table_name(table),
+            columns.join(", ")
+        ))
+    }
+}
+
+/// The columns in the header of a CSV file, its first record, quoted with
+/// `"` and separated by `,` like `COPY` reads them.
+fn csv_header(content: &str) -> Vec<String> {
+    let mut columns = vec![];
+    let mut column = String::new();
+    let mut quoted = false;
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                column.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => columns.push(std::mem::take(&mut column)),
+            '\n' | '\r' if !quoted => break,
+            c => column.push(c),
+        }
+    }
+    columns.push(column);
+    columns
+}
+
+/// Loads the fixtures in order in one transaction, the placeholders are
+/// replaced in SQL fixtures.
+pub(crate) async fn load_fixtures(
+    pool: &Pool,
+    fixtures: &[Fixture],
+    placeholders: &Placeholders,
+) -> Result<()> {
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+    for fixture in fixtures {
+        let failed = |e| ExecutionError::new(fixture.path.clone(), None, e);
+        if fixture.is_csv() {
+            let sink = transaction
+                .copy_in::<_, bytes::Bytes>(&fixture.copy_statement()?)
+                .await
+                .map_err(failed)?;
+            let mut sink = std::pin::pin!(sink);
+            sink.send(bytes::Bytes::from(fixture.content.clone()))
+                .await
+                .map_err(failed)?;
+            sink.finish().await.map_err(failed)?;
+        } else {
+            let sql = fill_template(&fixture.content, placeholders)?;
+            transaction.batch_execute(&sql).await.map_err(failed)?;
+        }
+    }
+    transaction.commit().await?;
+    Ok(())
+}
+
+#[test]
+fn csv_fixtures_are_copied_into_the_table_named_like_the_file() {
+    let fixture = Fixture {
+        path: "tests/fixtures/app.users.csv".to_string(),
+        content: "id,\"name\",a.b,\"x, \"\"y\"\"\"\r\n1,Ann,,\n".to_string(),
+    };
+    assert_eq!(
+        fixture.copy_statement().unwrap(),
+        r#"COPY "app"."users" ("id", "name", "a.b", "x, ""y""") FROM STDIN WITH (FORMAT csv, HEADER true)"#
+    );
+}
@@ -0,0 +1,118 @@
+use crate::dao::{SchemaHistoryRow, quote_table_name, schema_history_exists};
+use crate::error::{ConfigError, Result};
+use crate::migrator::{BASELINE_TYPE, DELETE_TYPE, RUST_TYPE, SQL_BASELINE_TYPE};
+use crc32fast::Hasher as Crc32Hasher;
+use deadpool_postgres::Client;
+use serde::Serialize;
+
+/// The default name of Flyway's history table.
+pub const FLYWAY_HISTORY_TABLE: &str = "flyway_schema_history";
+
+/// Types both tools record the same way.
+const SHARED_TYPES: [&str; 4] = [BASELINE_TYPE, DELETE_TYPE, SQL_BASELINE_TYPE, "SCHEMA"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryCopyReport {
+    pub from: String,
+    pub to: String,
+    /// Scripts of the copied rows in the order they were installed.
+    pub copied: Vec<String>,
+}
+
+/// The checksum Flyway records for a migration, a CRC32 of its lines without
+/// the line breaks.
+pub(crate) fn checksum(content: &str) -> i32 {
+    let mut hasher = Crc32Hasher::new();
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    for line in content.lines() {
+        hasher.update(line.as_bytes());
+    }
+    hasher.finalize() as i32
+}
+
+/// Translates a row of Flyway's history table into a row of pgmt's.
+pub(crate) fn import_row(table: &str, mut row: SchemaHistoryRow) -> Result<SchemaHistoryRow> {
+    row.r#type = match row.r#type.as_str() {
+        // Repeatable migrations have no version.
+        "SQL" if row.version.is_none() => "R",
+        "SQL" => "V",
+        "UNDO_SQL" => "U",
+        "JDBC" => RUST_TYPE,
+        r#type if SHARED_TYPES.contains(&r#type) => return Ok(row),
+        r#type => return Err(unsupported("import", table, &row, "pgmt", r#type)),
+    }
+    .to_string();
+    Ok(row)
+}
+
+/// Translates a row of pgmt's history table into a row of Flyway's.
+pub(crate) fn export_row(table: &str, mut row: SchemaHistoryRow) -> Result<SchemaHistoryRow> {
+    row.r#type = match row.r#type.as_str() {
+        "V" | "R" => "SQL",
+        "U" => "UNDO_SQL",
+        RUST_TYPE => "JDBC",
+        r#type if SHARED_TYPES.contains(&r#type) => return Ok(row),
+        r#type => return Err(unsupported("export", table, &row, "Flyway", r#type)),
+    }
+    .to_string();
+    Ok(row)
+}
+
+fn unsupported(
+    operation: &str,
+    table: &str,
+    row: &SchemaHistoryRow,
+    tool: &str,
+    r#type: &str,
+) -> crate::Error {
+    ConfigError::new(format!(
+        "Unable to {operation} row {} of {table}, {tool} has no {type} migrations like {}",
+        row.installed_rank, row.script
+    ))
+    .into()
+}
+
+/// Creates Flyway's history table the way Flyway creates it.
+pub(crate) async fn create_history_table_if_needed(client: &Client, table: &str) -> Result<()> {
+    if schema_history_exists(client, table).await? {
+        return Ok(());
+    }
+    let name = table.rsplit('.').next().unwrap_or(table);
+    let quoted = quote_table_name(table);
+    let pk = quote_table_name(&format!("{name}_pk"));
+    let index = quote_table_name(&format!("{name}_s_idx"));
+    client
+        .batch_execute(&format!(
+            r#"
+              CREATE TABLE {quoted} (
+                    installed_rank INT NOT NULL,
+                    version VARCHAR(50),
+                    description VARCHAR(200) NOT NULL,
+                    type VARCHAR(20) NOT NULL,
+                    script VARCHAR(1000) NOT NULL,
+                    checksum INTEGER,
+                    installed_by VARCHAR(100) NOT NULL,
+                    installed_on TIMESTAMP NOT NULL DEFAULT now(),
+                    execution_time INTEGER NOT NULL,
+                    success BOOLEAN NOT NULL
+              );
+              ALTER TABLE {quoted} ADD CONSTRAINT {pk} PRIMARY KEY (installed_rank);
+              CREATE INDEX {index} ON {quoted} (success);
+            "#
+        ))
+        .await?;
+    Ok(())
+}
+
+#[test]
+fn checksums_ignore_line_breaks_and_the_bom() {
+    let mut hasher = Crc32Hasher::new();
+    hasher.update(b"CREATE TABLE t1 (id INT);SELECT 1;");
+    let expected = hasher.finalize() as i32;
+    assert_eq!(checksum("CREATE TABLE t1 (id INT);\nSELECT 1;\n"), expected);
+    assert_eq!(
+        checksum("\u{feff}CREATE TABLE t1 (id INT);\r\nSELECT 1;"),
+        expected
+    );
+    assert_ne!(checksum("CREATE TABLE t1 (id INT);\n\nSELECT 2;"), expected);
+}
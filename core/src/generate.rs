@@ -0,0 +1,210 @@
+use crate::error::{ConfigError, Result};
+use crate::{DiscoveryOptions, MigrationVersion, parse_sql_files, read_sql_files};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+const TEMPLATE: &str = "-- ${description}\n";
+const UNDO_TEMPLATE: &str = "-- Undo ${description}\n";
+
+/// How `new_migration` creates the files.
+#[derive(Debug, Clone, Default)]
+pub struct NewMigrationOptions {
+    /// Use the current UTC time as the version, projects already versioned
+    /// by timestamps get one anyway.
+    pub timestamp: bool,
+    /// Also create the `U` file undoing the migration.
+    pub with_undo: bool,
+    /// Content of the new `V` file, `${version}` and `${description}` are
+    /// replaced, other placeholders are left for the migration.
+    pub template: Option<String>,
+    /// Content of the new `U` file, see `template`.
+    pub undo_template: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NewMigration {
+    pub version: String,
+    /// Paths of the created files.
+    pub files: Vec<String>,
+}
+
+/// Creates the next migration in `dir`, versioned after the latest migration
+/// the same way, so `1.0.9` is followed by `1.0.10`, `007` by `008` and a
+/// timestamp by the current timestamp.
+pub fn new_migration(
+    dir: &str,
+    description: &str,
+    options: &NewMigrationOptions,
+) -> Result<NewMigration> {
+    let slug = slugify(description);
+    if slug.is_empty() {
+        return Err(ConfigError::new(format!(
+            "The description `{description}` has no letters or digits to name the migration with"
+        ))
+        .into());
+    }
+    let latest = if Path::new(dir).exists() {
+        parse_sql_files(read_sql_files(vec![dir], &DiscoveryOptions::default())?)?
+            .into_iter()
+            // A baseline is the latest version when the migrations it
+            // squashed were archived.
+            .filter(|file| file.prefix == "V" || file.prefix == "B")
+            // The version as written in the file name, `2_1` is stored as
+            // `2.1`.
+            .filter_map(|file| {
+                let (version, _) = file.file_name[1..].split_once("__")?;
+                Some((file.migration_version()?, version.to_string()))
+            })
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, version)| version)
+    } else {
+        fs::create_dir_all(dir).map_err(|e| write_error(dir, e))?;
+        None
+    };
+    let version = next_version(latest.as_deref(), options.timestamp, Utc::now());
+    let description = slug.replace('_', " ");
+
+    let mut files = vec![];
+    let mut templates = vec![("V", options.template.as_deref().unwrap_or(TEMPLATE))];
+    if options.with_undo {
+        templates.push((
+            "U",
+            options.undo_template.as_deref().unwrap_or(UNDO_TEMPLATE),
+        ));
+    }
+    for (prefix, template) in templates {
+        let path = Path::new(dir).join(format!("{prefix}{version}__{slug}.sql"));
+        let path = path.to_string_lossy().to_string();
+        let content = template
+            .replace("${version}", &version)
+            .replace("${description}", &description);
+        // Never overwrite a migration, it may already be applied somewhere.
+        fs::File::create_new(&path)
+            .and_then(|mut file| file.write_all(content.as_bytes()))
+            .map_err(|e| write_error(&path, e))?;
+        files.push(path);
+    }
+    Ok(NewMigration { version, files })
+}
+
+pub(crate) fn write_error(path: &str, error: std::io::Error) -> ConfigError {
+    ConfigError {
+        message: format!("Unable to create {path}: {error}"),
+        source: Some(Box::new(error)),
+    }
+}
+
+/// Turns a description into the `Description_with_underscores` form of the
+/// migration file names.
+pub(crate) fn slugify(description: &str) -> String {
+    let words: Vec<&str> = description
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    let slug = words.join("_");
+    let mut chars = slug.chars();
+    match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => slug,
+    }
+}
+
+fn next_version(latest: Option<&str>, timestamp: bool, now: DateTime<Utc>) -> String {
+    let is_timestamp =
+        |version: &str| version.len() >= 8 && version.bytes().all(|b| b.is_ascii_digit());
+    if timestamp || latest.is_some_and(is_timestamp) {
+        let version = now.format("%Y%m%d%H%M%S").to_string();
+        return match latest {
+            // Two migrations created within the same second.
+            Some(latest) if parse(latest) >= parse(&version) => increment(latest),
+            _ => version,
+        };
+    }
+    latest.map_or_else(|| "1".to_string(), increment)
+}
+
+fn parse(version: &str) -> Option<MigrationVersion> {
+    version.parse().ok()
+}
+
+/// Increments the last part of the version keeping its zero padding.
+fn increment(version: &str) -> String {
+    let end = version
+        .trim_end_matches(|c: char| !c.is_ascii_digit())
+        .len();
+    let start = version[..end]
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map_or(0, |i| i + 1);
+    let mut digits: Vec<u8> = version[start..end].bytes().collect();
+    let mut carry = true;
+    for digit in digits.iter_mut().rev() {
+        if *digit == b'9' {
+            *digit = b'0';
+        } else {
+            *digit += 1;
+            carry = false;
+            break;
+        }
+    }
+    if carry {
+        digits.insert(0, b'1');
+    }
+    format!(
+        "{}{}{}",
+        &version[..start],
+        String::from_utf8(digits).unwrap(),
+        &version[end..]
+    )
+}
+
+#[test]
+fn slugifies_descriptions() {
+    assert_eq!(slugify("add users table"), "Add_users_table");
+    assert_eq!(
+        slugify("  Add `email` to users -- again!"),
+        "Add_email_to_users_again"
+    );
+    assert_eq!(slugify("ça"), "A");
+    assert_eq!(slugify("!!"), "");
+}
+
+#[test]
+fn versions_follow_the_latest_migration() {
+    let now = "2024-01-05T10:20:30Z".parse::<DateTime<Utc>>().unwrap();
+    assert_eq!(next_version(None, false, now), "1");
+    assert_eq!(next_version(Some("1.0.9"), false, now), "1.0.10");
+    assert_eq!(next_version(Some("2_1"), false, now), "2_2");
+    assert_eq!(next_version(Some("007"), false, now), "008");
+    assert_eq!(next_version(Some("99"), false, now), "100");
+    assert_eq!(next_version(None, true, now), "20240105102030");
+    assert_eq!(next_version(Some("3"), true, now), "20240105102030");
+    assert_eq!(next_version(Some("20231231"), false, now), "20240105102030");
+    assert_eq!(
+        next_version(Some("20240105102030"), false, now),
+        "20240105102031"
+    );
+}
+
+#[test]
+fn new_migrations_keep_the_style_of_the_version() {
+    let dir = std::env::temp_dir().join(format!("pgmt_underscores_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("V2_1__Add_users.sql"), "SELECT 1;").unwrap();
+    let dir = dir.to_string_lossy().to_string();
+
+    let created = new_migration(&dir, "add email", &NewMigrationOptions::default()).unwrap();
+    assert_eq!(created.version, "2_2");
+    assert_eq!(
+        created.files,
+        vec![
+            Path::new(&dir)
+                .join("V2_2__Add_email.sql")
+                .to_string_lossy()
+                .to_string()
+        ]
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
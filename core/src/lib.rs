@@ -1,22 +1,76 @@
+//! Postgres migrations, Flyway compatible.
+//!
+//! TLS comes from the `rustls` or the `native-tls` feature, only one of them
+//! can be enabled.
+
+macro_rules! to_sql_params {
+    ($($x:expr),* $(,)?) => {
+        &[$(
+            &$x as &(dyn ToSql + Sync)
+        ),*]
+    };
+}
+
+mod conninfo;
 mod dao;
+pub mod discovery;
+mod embedded;
 mod error;
+mod fixture;
+mod flyway;
+mod flyway_config;
+mod generate;
+mod lint;
+mod migrator;
+mod observer;
+mod retry;
+mod rust_migration;
+mod session;
+mod squash;
 mod template;
+mod test_database;
+mod test_template;
 pub mod tests_helper;
-use crate::dao::get_schema_history_rows;
-pub use crate::error::{ChecksumMismatchError, Error, Result};
-use chrono::Utc;
+mod tls;
+mod version;
+use crate::conninfo::ConnectionSettings;
+pub use crate::conninfo::redact_password;
+pub use crate::discovery::DiscoveryOptions;
+use crate::discovery::{is_hook, read_sql_files, validate_file_name};
+pub use crate::embedded::{EmbeddedFile, EmbeddedMigrations};
+pub use crate::error::{
+    BoxError, ChecksumMismatchError, ConfigError, ConnectionError, DiscoveryError, DumpError,
+    DuplicateVersionError, Error, ErrorCategory, ExecutionError, FailedMigrationError,
+    HistoryCorruptionError, HistoryNotEmptyError, InvalidMigrationFile, InvalidMigrationFilesError,
+    LockError, MissingMigrationError, MissingUndoError, MissingVariableTemplateError,
+    OutOfOrderError, Result, RustMigrationError, SquashedMigrationsError,
+};
+pub use crate::fixture::FIXTURE_EXTENSIONS;
+pub use crate::flyway::{FLYWAY_HISTORY_TABLE, HistoryCopyReport};
+pub use crate::flyway_config::{FLYWAY_CONFIG_FILES, FlywayConfig};
+pub use crate::generate::{NewMigration, NewMigrationOptions, new_migration};
+pub use crate::lint::{LINT_RULES, LintFinding, LintOptions, LintReport, LintRule};
+pub use crate::migrator::{
+    AppliedMigration, BaselineReport, InfoReport, MigrateReport, MigrationInfo, MigrationState,
+    Migrator, RepairReport, SkippedMigration, TransactionMode, UndoReport, ValidateReport,
+    ValidationIssue,
+};
+pub use crate::observer::{MigrationEvent, MigrationObserver};
+pub use crate::retry::ConnectOptions;
+use crate::rust_migration::RustMigrationRef;
+pub use crate::rust_migration::{RustMigration, Transaction};
+pub use crate::session::SessionSettings;
+pub use crate::squash::{SquashOptions, SquashReport};
+use crate::test_database::DatabaseGuard;
+pub use crate::test_database::{TestContext, TestDbGcReport, drop_test_databases};
+pub use crate::tls::{SslMode, TlsConfig};
+pub use crate::version::MigrationVersion;
+pub use async_trait::async_trait;
 use crc32fast::Hasher as Crc32Hasher;
+use deadpool_postgres::Client;
 pub use deadpool_postgres::Pool;
-use deadpool_postgres::{Client, Config, ManagerConfig, RecyclingMethod, Runtime};
 use dotenvy::dotenv;
-use std::collections::HashMap;
-use std::fs;
-use std::io;
-use std::path::{Path, PathBuf};
-use template::fill_template;
-use tokio_postgres::types::ToSql;
-use tokio_postgres::NoTls; // Adjust module path
-use url::Url;
+use std::collections::{BTreeMap, HashMap};
 
 pub type Placeholders = HashMap<String, String>;
 
@@ -28,35 +82,40 @@ macro_rules! vec_of_string {
     };
 }
 
-macro_rules! to_sql_params {
-    ($($x:expr),* $(,)?) => {
-        &[$(
-            &$x as &(dyn ToSql + Sync)
-        ),*]
-    };
-}
-
 use std::future::Future;
+use std::path::Path;
 
+/// Migrates the database with the files found in the migration locations.
+///
+/// The locations are scanned recursively, files skipped by the `options`
+/// ignore and exclude patterns are left alone, any other file that is not a
+/// valid migration or hook fails the migration.
 pub async fn migration_dirs<P>(
     migrations: Vec<P>,
     url: String,
     placeholders: Placeholders,
+    options: DiscoveryOptions,
 ) -> Result<()>
 where
     P: Into<String>,
 {
-    let migrations: Vec<String> = migrations.into_iter().map(Into::into).collect();
-    let cfg = new_cfg(url);
-    let files = read_sql_files(migrations.clone())?;
-    println!("files: {files:#?}");
+    let cfg = new_cfg(url)?;
     let pool = create_pool(&cfg).await?;
-    migrate(&pool, files, placeholders).await?;
+    Migrator::new()
+        .locations(migrations)
+        .discovery(options)
+        .placeholders(placeholders)
+        .migrate(&pool)
+        .await?;
     Ok(())
 }
 
 /// test_helper is a test helper that provisions a new database and migrates with the migrataion
-/// paths provided and does a cleanup after the callback has compleated it's execution.
+/// paths provided and does a cleanup after the callback has compleated it's execution, or
+/// panicked.
+///
+/// The migrations run once into a `pgmt_template_*` database, kept until they or the
+/// placeholders change, every test database is a copy of it.
 pub async fn test_migration<F, Fut, P, Output>(
     migrations: Vec<P>,
     placeholders: Option<Placeholders>,
@@ -66,21 +125,114 @@ where
     P: Into<String>,
     F: FnOnce(Pool) -> Fut,
     Fut: Future<Output = Output>,
+{
+    test_migration_with_context(migrations, placeholders, async |context| {
+        callback(context.pool).await
+    })
+    .await
+}
+
+/// Like `test_migration`, the callback also gets the URL and name of the test database and the
+/// placeholders it was migrated with.
+pub async fn test_migration_with_context<F, Fut, P, Output>(
+    migrations: Vec<P>,
+    placeholders: Option<Placeholders>,
+    callback: F,
+) -> Output
+where
+    P: Into<String>,
+    F: FnOnce(TestContext) -> Fut,
+    Fut: Future<Output = Output>,
+{
+    test_migration_with_fixtures(migrations, placeholders, Vec::<&Path>::new(), callback).await
+}
+
+/// Like `test_migration_with_context`, the SQL and CSV fixtures are loaded in order after the
+/// migrations, see `FIXTURE_EXTENSIONS`. They are not recorded in the history table.
+pub async fn test_migration_with_fixtures<F, Fut, P, Fx, Output>(
+    migrations: Vec<P>,
+    placeholders: Option<Placeholders>,
+    fixtures: Vec<Fx>,
+    callback: F,
+) -> Output
+where
+    P: Into<String>,
+    Fx: AsRef<Path>,
+    F: FnOnce(TestContext) -> Fut,
+    Fut: Future<Output = Output>,
 {
     dotenv().ok();
+    let fixtures: Vec<fixture::Fixture> = fixtures
+        .into_iter()
+        .map(|path| fixture::Fixture::read(path.as_ref()).unwrap())
+        .collect();
     let migrations: Vec<String> = migrations.into_iter().map(Into::into).collect();
-    let db_url = std::env::var("PGMT_TEST_DB_URL")
-        .expect("Environment variable PGMT_TEST_DB_URL is not set!");
+    let placeholders = placeholders.unwrap_or_default();
+    let db_url = test_db_url();
     let db_name = generate_temp_db_name();
-    let cfg = new_cfg(db_url.clone());
-    let pool = setup(cfg.clone(), db_name.clone()).await.unwrap();
-    let files = read_sql_files(migrations.clone()).unwrap();
-    migrate(&pool, files, placeholders.unwrap_or_default())
+    let cfg = new_cfg(db_url.clone()).unwrap();
+    let files = read_sql_files(migrations.clone(), &DiscoveryOptions::default()).unwrap();
+    test_template::create_from_template(&cfg, &db_name, files, placeholders.clone(), &fixtures)
         .await
         .unwrap();
-    let result = callback(pool).await;
-    teardown(db_url, &db_name).await.unwrap();
-    result
+    let _guard = DatabaseGuard::new(&db_url, &db_name);
+    let pool = database_pool(cfg, &db_name).await.unwrap();
+    callback(TestContext {
+        pool,
+        url: conninfo::database_url(&db_url, &db_name).unwrap(),
+        db_name,
+        placeholders,
+    })
+    .await
+}
+
+/// Runs the callback in a transaction that is always rolled back, instead of creating a
+/// database per test. The database is created once per test binary, from the template of
+/// the migrations, placeholders and fixtures, and shared by its tests with the same ones.
+/// Changes made outside of the transaction, like `nextval`, are seen by the other tests.
+/// The next test run drops it, or `pgmt test-db gc` does.
+pub async fn test_in_transaction<F, P, Fx, Output>(
+    migrations: Vec<P>,
+    placeholders: Option<Placeholders>,
+    fixtures: Vec<Fx>,
+    callback: F,
+) -> Output
+where
+    P: Into<String>,
+    Fx: AsRef<Path>,
+    F: AsyncFnOnce(&Transaction<'_>) -> Output,
+{
+    use futures_util::FutureExt;
+    use std::panic::AssertUnwindSafe;
+
+    dotenv().ok();
+    let fixtures: Vec<fixture::Fixture> = fixtures
+        .into_iter()
+        .map(|path| fixture::Fixture::read(path.as_ref()).unwrap())
+        .collect();
+    let migrations: Vec<String> = migrations.into_iter().map(Into::into).collect();
+    let cfg = new_cfg(test_db_url()).unwrap();
+    let files = read_sql_files(migrations, &DiscoveryOptions::default()).unwrap();
+    let client =
+        test_template::shared_client(cfg, files, placeholders.unwrap_or_default(), fixtures)
+            .await
+            .unwrap();
+    client.batch_execute("BEGIN").await.unwrap();
+    let result = AssertUnwindSafe(callback(&Transaction::new(&client)))
+        .catch_unwind()
+        .await;
+    match result {
+        Ok(output) => {
+            client.batch_execute("ROLLBACK").await.unwrap();
+            output
+        }
+        Err(panic) => {
+            // Closing the connection rolls back, instead of handing the open
+            // transaction to the next test.
+            drop(Client::take(client));
+            std::panic::resume_unwind(panic)
+        }
+    }
 }
 
 /// test_db creata a new test dba and gives the user both a connecion to the database and the
@@ -91,26 +243,24 @@ where
     Fut: Future<Output = Output>,
 {
     dotenv().ok();
-    let db_url = std::env::var("PGMT_TEST_DB_URL")
-        .expect("Environment variable PGMT_TEST_DB_URL is not set!");
-
-    let mut url = Url::parse(&db_url).expect("Failed to parse URL");
+    let db_url = test_db_url();
     let db_name = generate_temp_db_name();
-    url.set_path(&db_name);
+    let url = conninfo::database_url(&db_url, &db_name).unwrap();
     println!("###############################################");
     println!("URL: {}", url);
     println!("###############################################");
-    let cfg = new_cfg(db_url.clone());
-    let pool = setup(cfg.clone(), db_name.clone()).await.unwrap();
-    let result = callback(pool, url.to_string()).await;
-    teardown(db_url, &db_name).await.unwrap();
-    result
+    let cfg = new_cfg(db_url.clone()).unwrap();
+    create_database(&cfg, &db_name).await.unwrap();
+    let _guard = DatabaseGuard::new(&db_url, &db_name);
+    let pool = database_pool(cfg, &db_name).await.unwrap();
+    callback(pool, url).await
 }
 
 /// migrate_files is a test helper that is most usefull just to test pgmt itself.
 ///
 /// It provisions a new database and migrates with the migrataion
-/// paths provided and does a cleanup after the callback has compleated it's execution.
+/// paths provided and does a cleanup after the callback has compleated it's execution, or
+/// panicked.
 pub async fn migrate_files<F, Fut, Output>(
     files: Vec<SqlFile>,
     placeholders: Option<Placeholders>,
@@ -121,170 +271,125 @@ where
     Fut: Future<Output = Output>,
 {
     dotenv().ok();
-    let db_url = std::env::var("PGMT_TEST_DB_URL")
-        .expect("Environment variable PGMT_TEST_DB_URL is not set!");
+    let db_url = test_db_url();
     let db_name = generate_temp_db_name();
-    let cfg = new_cfg(db_url.clone());
-    let pool = setup(cfg.clone(), db_name.clone()).await.unwrap();
+    let cfg = new_cfg(db_url.clone()).unwrap();
+    create_database(&cfg, &db_name).await.unwrap();
+    let _guard = DatabaseGuard::new(&db_url, &db_name);
+    let pool = database_pool(cfg, &db_name).await.unwrap();
 
     migrate(&pool, files, placeholders.unwrap_or_default())
         .await
         .unwrap();
-    let result = callback(pool).await;
-    teardown(db_url, &db_name).await.unwrap();
-    result
+    callback(pool).await
 }
 
-async fn setup(mut cfg: Config, db_name: String) -> Result<Pool> {
-    {
-        let sql = format!("CREATE DATABASE {db_name}");
-        get_client(&create_pool(&cfg).await?)
-            .await?
-            .execute(&sql, &[])
-            .await?;
-    }
-    cfg.dbname = Some(db_name.clone());
-    create_pool(&cfg).await
+async fn setup(cfg: ConnectionSettings, db_name: String) -> Result<Pool> {
+    create_database(&cfg, &db_name).await?;
+    database_pool(cfg, &db_name).await
+}
+
+async fn create_database(cfg: &ConnectionSettings, db_name: &str) -> Result<()> {
+    let sql = format!("CREATE DATABASE {db_name}");
+    get_client(&create_pool(cfg).await?)
+        .await?
+        .execute(&sql, &[])
+        .await?;
+    Ok(())
 }
 
-async fn create_pool(cfg: &Config) -> Result<Pool> {
-    let pool: Pool = cfg
-        .create_pool(Some(Runtime::Tokio1), NoTls)
-        .map_err(|e| -> Error {
-            format!("Unable to get a connection from the pool: {e}").into()
-        })?;
+/// A pool of the database `db_name` on the server of `cfg`.
+async fn database_pool(mut cfg: ConnectionSettings, db_name: &str) -> Result<Pool> {
+    cfg.config.dbname(db_name);
+    create_pool(&cfg).await
+}
 
-    Ok(pool)
+async fn create_pool(cfg: &ConnectionSettings) -> Result<Pool> {
+    tls::create_pool(cfg.config.clone(), &cfg.tls)
 }
 
 async fn get_client(pool: &Pool) -> Result<Client> {
-    pool.get()
-        .await
-        .map_err(|e| -> Error { format!("Unable to get a connection from the pool: {e}").into() })
+    Ok(pool.get().await?)
 }
 
+/// Applies the pending migrations in the files, see `Migrator` for more
+/// options.
 pub async fn migrate(
     pool: &Pool,
     files: Vec<SqlFile>,
 
     placeholders: HashMap<String, String>,
 ) -> Result<()> {
-    let files: Vec<SqlInnerFile> = files.into_iter().map(SqlInnerFile::from).collect();
-    let files = sort_sql_files(files);
-    let files: Vec<SqlInnerFile> = files
-        .into_iter()
-        .filter(|f| matches!(f.kind(), Some(SqlFileKind::V(_))))
-        .collect();
-    println!("files X: {files:#?}");
-
-    let client = get_client(pool).await?;
-    create_schema_history_if_needed(&client).await?;
-    let files = filter_out_and_verify_privious_migrations(&client, files).await?;
-
-    println!("files: {files:#?}");
-    for file in &files {
-        // running each migration in order.
-        // TODO: wrap this in a transaction
-        // TODO: update the history table.
-        // TODO: Make this available from the cli
-        // TODO: Make this also do the beforeMigration hooks
-        // TODO: Make this also do the Repeatable migrations if needed
-        // TODO: expand placeholders from config
-        // TODO: expand placeholders from enviroment variables
-        // TODO: expand placeholders from config file ?? TOML
-        // TODO: Allow rollback
-        // TODO: Add front-matter into SQL file to allow disable transactions
-        // TODO: Add front-matter into SQL file to allow disable transactions
-        // TODO: Add partal update for only new migrations
-        // TODO: Fail if the is a second pass and the checksum of the prefious pass failed
-        // TODO: Fail if any of the migrations has been remove from the filesystem
-        // TODO: Enshure the beforeMigration is run before any other migrataion and before the
-        // checksum and exists checks
-
-        client.query("BEGIN;", &[]).await?;
-
-        let insert_into_schema_history_sql = r#"
-           insert into _schema_history
-                ( version
-                , description
-                , type
-                , script
-                , checksum
-                , installed_by
-                , installed_on
-                , execution_time
-                , success
-                )
-           VALUES
-                (  $1 -- version
-                ,  $2 -- description
-                ,  $3 -- type
-                ,  $4 -- script
-                ,  $5 -- checksum
-                ,  $6 -- installed_by
-                ,  $7 -- installed_on
-                ,  $8 -- execution_time
-                ,  $9 -- success
-              )
-        "#;
-
-        client
-            .execute(
-                insert_into_schema_history_sql,
-                to_sql_params![
-                    file.version,     // version
-                    file.description, // description
-                    file.prefix,      // type
-                    file.file_name,   // script
-                    file.checksum,    // checksum
-                    "installed_by",   // installed_by
-                    Utc::now(),       // installed_on
-                    0,                // execution_time
-                    true,             // success
-                ],
-            )
-            .await?;
-
-        let content = fill_template(&file.content, &placeholders)?;
-        // We should handle error whew better and do a rollback if we can
-        match client.batch_execute(&content).await {
-            Ok(_) => {
-                println!("OK");
-                client.query("COMMIT;", &[]).await?;
-                Ok(())
-            }
-            Err(e) => {
-                println!("error {}", e);
-                client.query("ROLLBACK;", &[]).await?;
-                Err(e)
-            }
-        }?;
-    }
+    Migrator::new()
+        .sources(files)
+        .placeholders(placeholders)
+        .migrate(pool)
+        .await?;
     Ok(())
 }
 
+/// Creates a connection pool for the database url or `key=value` connection
+/// string, settings it leaves out are taken from the service, the `PG*`
+/// environment variables and `~/.pgpass` like libpq does. An empty string
+/// only uses those.
+pub async fn connect(url: String) -> Result<Pool> {
+    create_pool(&new_cfg(url)?).await
+}
+
+/// Like `connect` but waits until a connection can be made, retrying with
+/// the `options` while the database is starting.
+pub async fn connect_with_options(url: String, options: &ConnectOptions) -> Result<Pool> {
+    let mut cfg = new_cfg(url)?;
+    if let Some(timeout) = options.timeout {
+        cfg.config.connect_timeout(timeout);
+    }
+    let pool = create_pool(&cfg).await?;
+    retry::wait_until_ready(&pool, options).await?;
+    Ok(pool)
+}
+
+/// Like `connect` with TLS settings that replace the `ssl` settings of the
+/// connection string and the environment.
+pub async fn connect_with_tls(url: String, tls: TlsConfig) -> Result<Pool> {
+    let mut cfg = new_cfg(url)?;
+    cfg.tls = tls;
+    create_pool(&cfg).await
+}
+
 pub async fn teardown(db_url: String, db_name: &str) -> Result<()> {
-    get_client(&create_pool(&new_cfg(db_url)).await?)
+    get_client(&create_pool(&new_cfg(db_url)?).await?)
         .await?
         .execute(&format!("DROP DATABASE {} WITH (FORCE)", db_name), &[])
         .await?;
     Ok(())
 }
 
-fn new_cfg(db_url: String) -> Config {
-    let mut cfg = Config::new();
-    cfg.url = Some(db_url);
-    cfg.manager = Some(ManagerConfig {
-        recycling_method: RecyclingMethod::Fast,
-    });
-    cfg
+fn new_cfg(db_url: String) -> Result<ConnectionSettings> {
+    conninfo::resolve(&db_url)
+}
+
+/// `PGMT_TEST_DB_URL`, or only the `PG*` environment variables when it is not
+/// set.
+fn test_db_url() -> String {
+    std::env::var("PGMT_TEST_DB_URL").unwrap_or_default()
 }
 
 fn generate_temp_db_name() -> String {
+    let name = temp_db_name();
+    // The test harness names the thread of a test after the test.
+    match std::thread::current().name() {
+        Some(test) if test != "main" => test_database::with_test_name(name, test),
+        _ => name,
+    }
+}
+
+/// `pgmt_test_{timestamp}_{letters}`, the name of a test database without its
+/// test.
+fn temp_db_name() -> String {
     use chrono::Local;
     use rand::Rng;
     let now = Local::now();
-    let timestamp = now.format("%Y_%m_%d_%H_%M_%S").to_string();
+    let timestamp = now.format(test_database::TIMESTAMP_FORMAT).to_string();
 
     let mut rng = rand::rng();
     let letters = b"abcdefghijklmnopqrstuvwxyz";
@@ -295,7 +400,12 @@ fn generate_temp_db_name() -> String {
         })
         .collect();
 
-    format!("pgmt_test_{}_{}", timestamp, rand_string)
+    format!(
+        "{}{}_{}",
+        test_database::TEST_DB_PREFIX,
+        timestamp,
+        rand_string
+    )
 }
 
 #[derive(Debug, Clone)]
@@ -313,41 +423,39 @@ pub struct SqlInnerFile {
     pub content: String,
     pub checksum: i32,
     pub file_name: String,
-    // TODO: should we delete the path here. I't probablay dose not matter.
-    // It could be come usefull for error reporting later on.
-    #[allow(dead_code)]
+    /// Where the file was read from, `lint` reports findings with it. The
+    /// file name for Rust migrations.
     pub file_path: String,
     pub prefix: String,
     pub version: Option<String>,
     pub description: String,
+    /// Session settings from the comments at the top of the file.
+    pub settings: SessionSettings,
+    /// The migration to run instead of the content, for Rust migrations.
+    pub(crate) rust: Option<RustMigrationRef>,
 }
 
-impl From<SqlFile> for SqlInnerFile {
-    fn from(file: SqlFile) -> Self {
+impl TryFrom<SqlFile> for SqlInnerFile {
+    type Error = InvalidMigrationFile;
+
+    fn try_from(file: SqlFile) -> std::result::Result<Self, Self::Error> {
         let SqlFile {
             content,
             file_name,
             file_path,
         } = file;
 
-        let version: Option<String>;
-        let prefix: String;
-        if let Some(rest) = file_name.strip_prefix('U') {
-            version = Some(rest.split("__").next().unwrap().to_string());
-            prefix = "U".to_string();
-        } else if let Some(rest) = file_name.strip_prefix('V') {
-            version = Some(rest.split("__").next().unwrap().to_string());
-            prefix = "V".to_string();
-        } else if let Some(rest) = file_name.strip_prefix('R') {
-            version = Some(rest.split("__").next().unwrap().to_string());
-            prefix = "U".to_string();
-        } else {
-            panic!("Unsuported prefix in {file_name}");
-        };
+        if let Err(reason) = validate_file_name(&file_name) {
+            return Err(InvalidMigrationFile { file_path, reason });
+        }
 
-        let mut hasher = Crc32Hasher::new();
-        hasher.update(b"foo bar baz");
-        let checksum = hasher.finalize() as i32;
+        let (prefix, rest) = file_name.split_at(1);
+        let prefix = prefix.to_string();
+        // The version is stored in its dotted form, the same way Flyway does.
+        let version = rest
+            .split_once("__")
+            .and_then(|(version, _)| version.parse::<MigrationVersion>().ok())
+            .map(|version| version.to_string());
 
         let description = file_name
             .clone()
@@ -358,7 +466,18 @@ impl From<SqlFile> for SqlInnerFile {
         // Normalize line endings (CRLF → LF)
         let content = content.replace("\r\n", "\n");
 
-        Self {
+        // Edited migrations are found by their checksum, line endings
+        // alone do not change it.
+        let mut hasher = Crc32Hasher::new();
+        hasher.update(content.as_bytes());
+        let checksum = hasher.finalize() as i32;
+
+        let settings = match SessionSettings::from_front_matter(&content) {
+            Ok(settings) => settings,
+            Err(reason) => return Err(InvalidMigrationFile { file_path, reason }),
+        };
+
+        Ok(Self {
             content,
             file_name,
             file_path,
@@ -366,67 +485,84 @@ impl From<SqlFile> for SqlInnerFile {
             checksum,
             version,
             description,
-        }
+            settings,
+            rust: None,
+        })
     }
 }
 
-// TODO: Add tests for this guy
-fn read_sql_files<P>(dirs: Vec<P>) -> io::Result<Vec<SqlFile>>
-where
-    P: Into<String>,
-{
-    let mut files = Vec::new();
-    for dir in dirs {
-        let dir_str = dir.into();
-        let path = Path::new(&dir_str);
-
-        if path.is_dir() {
-            for entry in fs::read_dir(path)? {
-                let entry = entry?;
-                let file_path: PathBuf = entry.path();
-                let file_name: String = entry.file_name().to_str().unwrap().to_string();
-
-                if file_path.is_file() {
-                    if let Some(ext) = file_path.extension() {
-                        if ext.to_string_lossy().to_lowercase() == "sql" {
-                            let content = fs::read_to_string(&file_path)?;
-                            files.push(SqlFile {
-                                content,
-                                file_name,
-                                file_path: file_path.to_string_lossy().to_string(),
-                            });
-                        }
-                    }
-                }
-            }
+/// Converts the files into migrations, skipping hooks and failing with every
+/// file that is not a valid migration.
+fn parse_sql_files(files: Vec<SqlFile>) -> Result<Vec<SqlInnerFile>> {
+    let mut parsed = Vec::new();
+    let mut invalid = Vec::new();
+    for file in files.into_iter().filter(|f| !is_hook(&f.file_name)) {
+        match SqlInnerFile::try_from(file) {
+            Ok(file) => parsed.push(file),
+            Err(file) => invalid.push(file),
         }
     }
+    if !invalid.is_empty() {
+        return Err(InvalidMigrationFilesError { files: invalid }.into());
+    }
+    verify_unique_versions(&parsed)?;
+    Ok(parsed)
+}
 
-    Ok(files)
+fn verify_unique_versions(files: &[SqlInnerFile]) -> Result<()> {
+    let mut by_version: BTreeMap<(String, MigrationVersion), Vec<String>> = BTreeMap::new();
+    for file in files {
+        if let Some(SqlFileKind::U(version) | SqlFileKind::B(version) | SqlFileKind::V(version)) =
+            file.kind()
+        {
+            by_version
+                .entry((file.prefix.clone(), version))
+                .or_default()
+                .push(file.file_path.clone());
+        }
+    }
+    if let Some(((prefix, version), file_paths)) =
+        by_version.into_iter().find(|(_, paths)| paths.len() > 1)
+    {
+        return Err(DuplicateVersionError {
+            prefix,
+            version: version.to_string(),
+            file_paths,
+        }
+        .into());
+    }
+    Ok(())
 }
 
-use semver::Version;
 use std::cmp::Ordering;
 
 // Extract a sort key enum
 #[derive(Debug)]
 enum SqlFileKind {
-    U(Version),
-    V(Version),
+    U(MigrationVersion),
+    B(MigrationVersion),
+    V(MigrationVersion),
     R(String),
 }
 
 impl SqlInnerFile {
+    fn migration_version(&self) -> Option<MigrationVersion> {
+        self.version.as_ref().and_then(|v| v.parse().ok())
+    }
+
     // TODO: Add stom tests for this
     fn kind(&self) -> Option<SqlFileKind> {
         let name = self.file_name.as_str();
 
         if let Some(rest) = name.strip_prefix('U') {
             let version_str = rest.split("__").next()?;
-            Version::parse(version_str).ok().map(SqlFileKind::U)
+            version_str.parse().ok().map(SqlFileKind::U)
+        } else if let Some(rest) = name.strip_prefix('B') {
+            let version_str = rest.split("__").next()?;
+            version_str.parse().ok().map(SqlFileKind::B)
         } else if let Some(rest) = name.strip_prefix('V') {
             let version_str = rest.split("__").next()?;
-            Version::parse(version_str).ok().map(SqlFileKind::V)
+            version_str.parse().ok().map(SqlFileKind::V)
         } else if name.starts_with('R') {
             Some(SqlFileKind::R(name.to_string()))
         } else {
@@ -439,11 +575,14 @@ fn sort_sql_files(mut files: Vec<SqlInnerFile>) -> Vec<SqlInnerFile> {
     files.sort_by(|a, b| {
         match (a.kind(), b.kind()) {
             (Some(SqlFileKind::U(a)), Some(SqlFileKind::U(b))) => a.cmp(&b),
+            (Some(SqlFileKind::B(a)), Some(SqlFileKind::B(b))) => a.cmp(&b),
             (Some(SqlFileKind::V(a)), Some(SqlFileKind::V(b))) => a.cmp(&b),
             (Some(SqlFileKind::R(a)), Some(SqlFileKind::R(b))) => a.cmp(&b),
-            // Ordering between kinds: U < V < R
+            // Ordering between kinds: U < B < V < R
             (Some(SqlFileKind::U(_)), _) => Ordering::Less,
             (_, Some(SqlFileKind::U(_))) => Ordering::Greater,
+            (Some(SqlFileKind::B(_)), _) => Ordering::Less,
+            (_, Some(SqlFileKind::B(_))) => Ordering::Greater,
             (Some(SqlFileKind::V(_)), _) => Ordering::Less,
             (_, Some(SqlFileKind::V(_))) => Ordering::Greater,
             _ => Ordering::Equal, // If one or both couldn't be classified
@@ -452,118 +591,3 @@ fn sort_sql_files(mut files: Vec<SqlInnerFile>) -> Vec<SqlInnerFile> {
 
     files
 }
-
-async fn create_schema_history_if_needed(client: &Client) -> Result<()> {
-    let sql = r#"
-        SELECT exists (
-            SELECT 1
-            FROM information_schema.tables
-            WHERE table_schema = 'public'  -- or another schema name
-              AND table_name = '_schema_history'
-        ) AS exists;
-    "#;
-    let exists: bool = client.query_one(sql, &[]).await?.try_get("exists")?;
-    if !exists {
-        create_schema_history_table(client).await?;
-    }
-    Ok(())
-}
-
-async fn create_schema_history_table(client: &Client) -> Result<()> {
-    client
-        .batch_execute(
-            r#"
-              CREATE TABLE _schema_history (
-                    -- Auto-incrementing rank (used as primary key and order of migration)
-                    installed_rank SERIAL PRIMARY KEY,
-
-                    -- Version of the migration (e.g., 1.2, 2.0)
-                    version VARCHAR(50),
-
-                    -- Human-readable description (e.g., Create users table)
-                    description VARCHAR(200) NOT NULL,
-
-                    -- Type of migration (SQL, JDBC, UNDO, etc.)
-                    type VARCHAR(20) NOT NULL,
-
-                    -- Filename of the migration script
-                    script VARCHAR(1000) NOT NULL,
-
-                    -- Checksum used to detect script changes
-                    checksum INT,
-
-                    -- Database user who ran the migration
-                    installed_by VARCHAR(100) NOT NULL,
-
-                    -- Timestamp when the migration was applied
-                    installed_on TIMESTAMPTZ NOT NULL DEFAULT now(),
-
-                    -- Time in milliseconds to execute the migration
-                    execution_time INT NOT NULL,
-
-                    -- Whether the migration succeeded (true) or failed (false)
-                    success BOOLEAN NOT NULL
-              );
-
-
-              COMMENT ON COLUMN _schema_history .installed_rank
-                   IS 'Execution order rank (primary key); increments with each migration';
-
-              COMMENT ON COLUMN _schema_history .version
-                   IS 'Version of the migration (e.g., 1.0, 2.1.3). Null for repeatable migrations';
-
-              COMMENT ON COLUMN _schema_history .description
-                   IS 'Human-readable description of the migration (e.g., Create users table)';
-
-              COMMENT ON COLUMN _schema_history .type
-                   IS 'Type of migration (e.g., SQL, JDBC, REPEATABLE, UNDO)';
-
-              COMMENT ON COLUMN _schema_history .script
-                   IS 'Name of the migration script file';
-
-              COMMENT ON COLUMN _schema_history .checksum
-                   IS 'Checksum of the migration script content to detect changes. Null for repeatable if not validated';
-
-              COMMENT ON COLUMN _schema_history .installed_by
-                   IS 'Database user who applied the migration';
-
-              COMMENT ON COLUMN _schema_history .installed_on
-                   IS 'Timestamp when the migration was applied. Defaults to current time';
-
-              COMMENT ON COLUMN _schema_history .execution_time
-                   IS 'Execution time of the migration in milliseconds';
-
-              COMMENT ON COLUMN _schema_history .success
-                   IS 'Whether the migration was successful (true) or failed (false)';
-
-
-            "#,
-        )
-        .await?;
-
-    Ok(())
-}
-
-async fn filter_out_and_verify_privious_migrations(
-    client: &Client,
-    files: Vec<SqlInnerFile>,
-) -> Result<Vec<SqlInnerFile>> {
-    let mut schema_history = get_schema_history_rows(client).await;
-    let mut result: Vec<SqlInnerFile> = vec![];
-
-    for file in files {
-        if schema_history.is_empty() {
-            result.push(file);
-        } else {
-            let history = schema_history.remove(0);
-            if file.checksum != history.checksum {
-                return Err(Error::ChecksumMismatchError(ChecksumMismatchError {
-                    file_name: file.file_name,
-                    file_checksum: file.checksum,
-                    applied_checksum: history.checksum,
-                }));
-            }
-        }
-    }
-    Ok(result)
-}
@@ -0,0 +1,479 @@
+use crate::SqlInnerFile;
+use crate::error::{ConfigError, Result};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+/// A check for an operation that blocks reads or writes of a table while it
+/// runs, or that breaks the application still running against the old
+/// schema.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct LintRule {
+    pub id: &'static str,
+    pub description: &'static str,
+}
+
+pub const CREATE_INDEX_NON_CONCURRENTLY: &str = "create-index-non-concurrently";
+pub const ALTER_COLUMN_TYPE: &str = "alter-column-type";
+pub const ADD_NOT_NULL_COLUMN_WITHOUT_DEFAULT: &str = "add-not-null-column-without-default";
+pub const ADD_CONSTRAINT_WITHOUT_NOT_VALID: &str = "add-constraint-without-not-valid";
+pub const DROP_COLUMN: &str = "drop-column";
+pub const RENAME: &str = "rename";
+
+/// Every rule `lint` checks.
+pub const LINT_RULES: &[LintRule] = &[
+    LintRule {
+        id: CREATE_INDEX_NON_CONCURRENTLY,
+        description: "CREATE INDEX without CONCURRENTLY blocks writes to the table while the index is built",
+    },
+    LintRule {
+        id: ALTER_COLUMN_TYPE,
+        description: "Changing the type of a column rewrites the table and blocks reads and writes while it does",
+    },
+    LintRule {
+        id: ADD_NOT_NULL_COLUMN_WITHOUT_DEFAULT,
+        description: "Adding a NOT NULL column without a default fails when the table has rows",
+    },
+    LintRule {
+        id: ADD_CONSTRAINT_WITHOUT_NOT_VALID,
+        description: "Adding a foreign key or check constraint without NOT VALID scans the table while holding a lock",
+    },
+    LintRule {
+        id: DROP_COLUMN,
+        description: "Dropping a column breaks the application still reading it",
+    },
+    LintRule {
+        id: RENAME,
+        description: "Renaming a table or column breaks the application still using the old name",
+    },
+];
+
+/// Which rules `lint` skips, besides the ones a file allows with a
+/// `-- pgmt-lint: allow rule, other-rule` comment.
+#[derive(Debug, Clone, Default)]
+pub struct LintOptions {
+    pub allow: Vec<String>,
+}
+
+/// An operation found by a rule.
+#[derive(Debug, Clone, Serialize)]
+pub struct LintFinding {
+    pub rule: &'static str,
+    pub script: String,
+    pub file_path: String,
+    /// Line the statement starts on, counting from 1.
+    pub line: usize,
+    pub message: String,
+    pub statement: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LintReport {
+    /// The migrations that were checked.
+    pub checked: Vec<String>,
+    pub findings: Vec<LintFinding>,
+}
+
+impl LintReport {
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+/// Checks the statements of the migrations, tables created by the migration
+/// itself are new and can be changed without blocking anyone.
+pub(crate) fn lint_files(files: &[&SqlInnerFile], options: &LintOptions) -> Result<LintReport> {
+    let allowed = rule_ids(options.allow.iter().map(String::as_str), "--allow")?;
+    let mut report = LintReport {
+        checked: vec![],
+        findings: vec![],
+    };
+    for file in files {
+        let mut file_allowed = rule_ids(allowed_in_file(&file.content), &file.file_name)?;
+        file_allowed.extend(&allowed);
+        let mut new_tables = HashSet::new();
+        for statement in split_statements(&file.content) {
+            for (rule, message) in check_statement(&statement.code, &mut new_tables) {
+                if file_allowed.contains(rule) {
+                    continue;
+                }
+                report.findings.push(LintFinding {
+                    rule,
+                    script: file.file_name.clone(),
+                    file_path: file.file_path.clone(),
+                    line: statement.line,
+                    message,
+                    statement: statement.text.clone(),
+                });
+            }
+        }
+        report.checked.push(file.file_name.clone());
+    }
+    Ok(report)
+}
+
+fn rule_ids<'a>(ids: impl Iterator<Item = &'a str>, origin: &str) -> Result<HashSet<&'static str>> {
+    ids.map(|id| {
+        LINT_RULES
+            .iter()
+            .find(|rule| rule.id == id)
+            .map(|rule| rule.id)
+            .ok_or_else(|| ConfigError::new(format!("Unknown lint rule `{id}` in {origin}")).into())
+    })
+    .collect()
+}
+
+/// Reads the rules allowed by `-- pgmt-lint: allow rule, other-rule` comments
+/// anywhere in the file.
+fn allowed_in_file(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .lines()
+        .filter_map(|line| {
+            line.trim()
+                .strip_prefix("--")?
+                .trim_start()
+                .strip_prefix("pgmt-lint:")?
+                .trim_start()
+                .strip_prefix("allow")
+        })
+        .flat_map(|rules| rules.split([',', ' ']).filter(|rule| !rule.is_empty()))
+}
+
+const NAME: &str = r#"((?:"[^"]*"|[\w${}]+)(?:\.(?:"[^"]*"|[\w${}]+))*)"#;
+
+fn regex(pattern: &str) -> Regex {
+    Regex::new(&format!("(?i){}", pattern.replace("NAME", NAME))).unwrap()
+}
+
+static CREATE_TABLE: LazyLock<Regex> = LazyLock::new(|| {
+    regex(
+        r"^CREATE (?:(?:GLOBAL |LOCAL )?(?:TEMP|TEMPORARY|UNLOGGED) )?TABLE (?:IF NOT EXISTS )?NAME",
+    )
+});
+static CREATE_INDEX: LazyLock<Regex> = LazyLock::new(|| {
+    regex(
+        r#"^CREATE (?:UNIQUE )?INDEX (CONCURRENTLY )?(?:IF NOT EXISTS )?(?:(?:"[^"]*"|[\w${}]+) )?ON (?:ONLY )?NAME"#,
+    )
+});
+static ALTER_TABLE: LazyLock<Regex> =
+    LazyLock::new(|| regex(r"^ALTER TABLE (?:IF EXISTS )?(?:ONLY )?NAME(?: \*)? (.*)$"));
+static ALTER_TYPE: LazyLock<Regex> =
+    LazyLock::new(|| regex(r#"^ALTER (?:COLUMN )?(?:"[^"]*"|\S+) (?:SET DATA )?TYPE\b"#));
+static ADD_TABLE_CONSTRAINT: LazyLock<Regex> = LazyLock::new(|| {
+    regex(
+        r#"^ADD (?:CONSTRAINT (?:"[^"]*"|\S+) )?(PRIMARY KEY|UNIQUE|FOREIGN KEY|CHECK|EXCLUDE)\b"#,
+    )
+});
+static ADD_COLUMN: LazyLock<Regex> = LazyLock::new(|| regex(r"^ADD\b"));
+static NOT_NULL: LazyLock<Regex> = LazyLock::new(|| regex(r"\bNOT NULL\b"));
+static HAS_DEFAULT: LazyLock<Regex> = LazyLock::new(|| {
+    regex(r"\b(?:DEFAULT|GENERATED|SMALLSERIAL|SERIAL|BIGSERIAL|SERIAL2|SERIAL4|SERIAL8)\b")
+});
+static NOT_VALID: LazyLock<Regex> = LazyLock::new(|| regex(r"\bNOT VALID\b"));
+static DROP_COLUMN_ACTION: LazyLock<Regex> = LazyLock::new(|| {
+    regex(r"^DROP\b(?: COLUMN\b| (?:IF EXISTS )?NAME(?: (?:CASCADE|RESTRICT))?$)")
+});
+static RENAME_ACTION: LazyLock<Regex> = LazyLock::new(|| regex(r"^RENAME\b"));
+static RENAME_CONSTRAINT: LazyLock<Regex> = LazyLock::new(|| regex(r"^RENAME CONSTRAINT\b"));
+
+/// Returns the rules a statement breaks, `new_tables` collects the tables
+/// created so far by the migration.
+fn check_statement(code: &str, new_tables: &mut HashSet<String>) -> Vec<(&'static str, String)> {
+    let mut found = vec![];
+    if let Some(caps) = CREATE_TABLE.captures(code) {
+        new_tables.insert(table_key(&caps[1]));
+    } else if let Some(caps) = CREATE_INDEX.captures(code) {
+        if caps.get(1).is_none() && !new_tables.contains(&table_key(&caps[2])) {
+            found.push((
+                CREATE_INDEX_NON_CONCURRENTLY,
+                format!(
+                    "Creating an index on {} blocks writes to it, use CREATE INDEX CONCURRENTLY in a migration without a transaction",
+                    &caps[2]
+                ),
+            ));
+        }
+    } else if let Some(caps) = ALTER_TABLE.captures(code) {
+        let table = &caps[1];
+        if new_tables.contains(&table_key(table)) {
+            return found;
+        }
+        for action in split_actions(&caps[2]) {
+            if ALTER_TYPE.is_match(action) {
+                found.push((
+                    ALTER_COLUMN_TYPE,
+                    format!("Changing the type of a column rewrites {table} and blocks reads and writes, add a new column and backfill it instead"),
+                ));
+            } else if let Some(constraint) = ADD_TABLE_CONSTRAINT.captures(action) {
+                let kind = constraint[1].to_uppercase();
+                if (kind == "FOREIGN KEY" || kind == "CHECK") && !NOT_VALID.is_match(action) {
+                    found.push((
+                        ADD_CONSTRAINT_WITHOUT_NOT_VALID,
+                        format!("Adding a {kind} constraint scans {table} while blocking writes, add it NOT VALID and VALIDATE CONSTRAINT in a later migration"),
+                    ));
+                }
+            } else if ADD_COLUMN.is_match(action) {
+                if NOT_NULL.is_match(action) && !HAS_DEFAULT.is_match(action) {
+                    found.push((
+                        ADD_NOT_NULL_COLUMN_WITHOUT_DEFAULT,
+                        format!("Adding a NOT NULL column without a default to {table} fails when it has rows, add a default or make the column nullable"),
+                    ));
+                }
+            } else if DROP_COLUMN_ACTION.is_match(action) {
+                found.push((
+                    DROP_COLUMN,
+                    format!("Dropping a column of {table} breaks the application still reading it, stop using the column before dropping it"),
+                ));
+            } else if RENAME_ACTION.is_match(action) && !RENAME_CONSTRAINT.is_match(action) {
+                found.push((
+                    RENAME,
+                    format!("Renaming {table} or one of its columns breaks the application still using the old name"),
+                ));
+            }
+        }
+    }
+    found
+}
+
+fn table_key(name: &str) -> String {
+    name.replace('"', "").to_lowercase()
+}
+
+/// Splits the actions of an `ALTER TABLE` on the commas outside parentheses.
+fn split_actions(actions: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in actions.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(actions[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(actions[start..].trim());
+    parts
+}
+
+struct Statement {
+    line: usize,
+    /// The statement as written.
+    text: String,
+    /// The statement without comments, string literals and function bodies,
+    /// and with the whitespace collapsed, which is what the rules look at.
+    code: String,
+}
+
+/// Splits a migration into statements, keeping the semicolons in quotes,
+/// comments and dollar quoted function bodies.
+fn split_statements(sql: &str) -> Vec<Statement> {
+    let mut statements = vec![];
+    let mut code = String::new();
+    let mut start = None;
+    let mut i = 0;
+    let mut finish = |code: &mut String, start: &mut Option<usize>, end: usize| {
+        if let Some(start) = start.take() {
+            statements.push(Statement {
+                line: sql[..start].matches('\n').count() + 1,
+                text: sql[start..end].trim().to_string(),
+                code: code.split_whitespace().collect::<Vec<_>>().join(" "),
+            });
+        }
+        code.clear();
+    };
+    while i < sql.len() {
+        let rest = &sql[i..];
+        if rest.starts_with("--") {
+            i += rest.find('\n').unwrap_or(rest.len());
+            code.push(' ');
+            continue;
+        }
+        if rest.starts_with("/*") {
+            i += block_comment_len(rest);
+            code.push(' ');
+            continue;
+        }
+        let c = rest.chars().next().unwrap();
+        if c == ';' {
+            finish(&mut code, &mut start, i);
+            i += 1;
+            continue;
+        }
+        if !c.is_whitespace() && start.is_none() {
+            start = Some(i);
+        }
+        match c {
+            '\'' => {
+                let escapes = code.ends_with(['E', 'e']);
+                i += quoted_len(rest, '\'', escapes);
+                code.push_str("''");
+            }
+            '"' => {
+                let len = quoted_len(rest, '"', false);
+                code.push_str(&rest[..len]);
+                i += len;
+            }
+            '$' if dollar_tag(rest).is_some() => {
+                let tag = dollar_tag(rest).unwrap();
+                i += rest[tag.len()..]
+                    .find(tag)
+                    .map_or(rest.len(), |end| end + 2 * tag.len());
+                code.push_str("$$");
+            }
+            _ => {
+                code.push(c);
+                i += c.len_utf8();
+            }
+        }
+    }
+    finish(&mut code, &mut start, sql.len());
+    statements
+}
+
+/// Length of a block comment, they can be nested.
+fn block_comment_len(rest: &str) -> usize {
+    let mut depth = 0;
+    let mut i = 0;
+    while i < rest.len() {
+        if rest[i..].starts_with("/*") {
+            depth += 1;
+            i += 2;
+        } else if rest[i..].starts_with("*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += rest[i..].chars().next().unwrap().len_utf8();
+        }
+    }
+    rest.len()
+}
+
+/// Length of a quoted string or identifier including the quotes, a doubled
+/// quote is part of it.
+fn quoted_len(rest: &str, quote: char, escapes: bool) -> usize {
+    let mut chars = rest.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        if escapes && c == '\\' {
+            chars.next();
+        } else if c == quote {
+            if chars.peek().is_some_and(|(_, next)| *next == quote) {
+                chars.next();
+            } else {
+                return i + 1;
+            }
+        }
+    }
+    rest.len()
+}
+
+/// The `$tag$` starting a dollar quoted string.
+fn dollar_tag(rest: &str) -> Option<&str> {
+    let end = rest[1..].find(|c: char| !(c.is_alphanumeric() || c == '_'))? + 1;
+    let tag = &rest[1..end];
+    if rest[end..].starts_with('$') && !tag.starts_with(|c: char| c.is_ascii_digit()) {
+        Some(&rest[..=end])
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+fn rules_of(sql: &str) -> Vec<&'static str> {
+    let mut new_tables = HashSet::new();
+    split_statements(sql)
+        .iter()
+        .flat_map(|statement| check_statement(&statement.code, &mut new_tables))
+        .map(|(rule, _)| rule)
+        .collect()
+}
+
+#[test]
+fn splits_statements() {
+    let statements = split_statements(
+        "-- a; comment\nCREATE TABLE t (a TEXT DEFAULT 'x;y');\n/* b; /* nested; */ */\nCREATE FUNCTION f() RETURNS int AS $body$ SELECT 1; $body$ LANGUAGE sql;\n\nSELECT E'\\';', \"a;b\"",
+    );
+    let codes: Vec<_> = statements.iter().map(|s| s.code.as_str()).collect();
+    assert_eq!(
+        codes,
+        [
+            "CREATE TABLE t (a TEXT DEFAULT '')",
+            "CREATE FUNCTION f() RETURNS int AS $$ LANGUAGE sql",
+            "SELECT E'', \"a;b\"",
+        ]
+    );
+    let lines: Vec<_> = statements.iter().map(|s| s.line).collect();
+    assert_eq!(lines, [2, 4, 6]);
+    assert_eq!(statements[0].text, "CREATE TABLE t (a TEXT DEFAULT 'x;y')");
+}
+
+#[test]
+fn finds_dangerous_operations() {
+    assert_eq!(
+        rules_of("CREATE INDEX i ON account (email)"),
+        [CREATE_INDEX_NON_CONCURRENTLY]
+    );
+    assert_eq!(
+        rules_of("create unique index on app.account(email)"),
+        [CREATE_INDEX_NON_CONCURRENTLY]
+    );
+    assert!(rules_of("CREATE INDEX CONCURRENTLY i ON account (email)").is_empty());
+    assert_eq!(
+        rules_of("ALTER TABLE account ALTER COLUMN id TYPE bigint"),
+        [ALTER_COLUMN_TYPE]
+    );
+    assert_eq!(
+        rules_of("ALTER TABLE account ALTER id SET DATA TYPE bigint"),
+        [ALTER_COLUMN_TYPE]
+    );
+    assert!(rules_of("ALTER TABLE account ALTER COLUMN id SET DEFAULT 1").is_empty());
+    assert_eq!(
+        rules_of("ALTER TABLE account ADD COLUMN email TEXT NOT NULL"),
+        [ADD_NOT_NULL_COLUMN_WITHOUT_DEFAULT]
+    );
+    assert!(rules_of("ALTER TABLE account ADD email TEXT NOT NULL DEFAULT ''").is_empty());
+    assert!(rules_of("ALTER TABLE account ADD COLUMN email TEXT").is_empty());
+    assert_eq!(
+        rules_of("ALTER TABLE account ADD CONSTRAINT fk FOREIGN KEY (org) REFERENCES org (id)"),
+        [ADD_CONSTRAINT_WITHOUT_NOT_VALID]
+    );
+    assert_eq!(
+        rules_of("ALTER TABLE account ADD CHECK (id > 0)"),
+        [ADD_CONSTRAINT_WITHOUT_NOT_VALID]
+    );
+    assert!(rules_of("ALTER TABLE account ADD CONSTRAINT c CHECK (id > 0) NOT VALID").is_empty());
+    assert_eq!(
+        rules_of("ALTER TABLE account DROP COLUMN email"),
+        [DROP_COLUMN]
+    );
+    assert_eq!(
+        rules_of("ALTER TABLE account DROP IF EXISTS email CASCADE"),
+        [DROP_COLUMN]
+    );
+    assert!(rules_of("ALTER TABLE account DROP CONSTRAINT c").is_empty());
+    assert_eq!(
+        rules_of("ALTER TABLE account RENAME COLUMN a TO b"),
+        [RENAME]
+    );
+    assert_eq!(rules_of("ALTER TABLE account RENAME TO users"), [RENAME]);
+    assert!(rules_of("ALTER TABLE account RENAME CONSTRAINT a TO b").is_empty());
+    assert_eq!(
+        rules_of("ALTER TABLE account DROP COLUMN a, ADD COLUMN b numeric(10, 2) NOT NULL"),
+        [DROP_COLUMN, ADD_NOT_NULL_COLUMN_WITHOUT_DEFAULT]
+    );
+}
+
+#[test]
+fn tables_created_by_the_migration_are_new() {
+    assert!(rules_of(
+        "CREATE TABLE \"Account\" (id int);\nCREATE INDEX i ON \"Account\" (id);\nALTER TABLE \"Account\" ADD COLUMN email TEXT NOT NULL;"
+    )
+    .is_empty());
+    assert!(rules_of("SELECT 'ALTER TABLE account DROP COLUMN a'").is_empty());
+}
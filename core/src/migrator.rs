@@ -0,0 +1,1513 @@
+use crate::dao::{
+    DEFAULT_HISTORY_TABLE, NewSchemaHistoryRow, SchemaHistoryRow, create_schema_history_if_needed,
+    get_schema_history_rows, insert_history_rows, insert_schema_history_row, quote_table_name,
+    schema_history_exists,
+};
+use crate::discovery::{is_hook, read_sql_files};
+use crate::error::{
+    ChecksumMismatchError, ConfigError, Error, ExecutionError, FailedMigrationError,
+    HistoryCorruptionError, HistoryNotEmptyError, LockError, MissingMigrationError,
+    MissingUndoError, OutOfOrderError, Result, SquashedMigrationsError,
+};
+use crate::flyway::{self, HistoryCopyReport};
+use crate::generate::{slugify, write_error};
+use crate::lint::{LintOptions, LintReport, lint_files};
+use crate::observer::{MigrationEvent, MigrationObserver, Observers};
+use crate::rust_migration::{RustMigration, RustMigrationRef};
+use crate::session::{self, SessionSettings};
+use crate::squash::{self, SquashOptions, SquashReport};
+use crate::template::fill_template;
+use crate::{
+    DiscoveryOptions, EmbeddedMigrations, MigrationVersion, Placeholders, Pool, SqlFile,
+    SqlInnerFile, get_client, parse_sql_files, sort_sql_files, verify_unique_versions,
+};
+use chrono::{DateTime, Utc};
+use crc32fast::Hasher as Crc32Hasher;
+use deadpool_postgres::Client;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::future::Future;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_postgres::error::SqlState;
+use tracing::{Instrument, debug, error, info, info_span, warn};
+
+/// History table type of a row recording a baseline.
+pub const BASELINE_TYPE: &str = "BASELINE";
+/// History table type of a row recording that a missing migration was removed
+/// from the history by `repair`.
+pub const DELETE_TYPE: &str = "DELETE";
+/// History table type of a baseline migration, a `B` file applied to an empty
+/// database instead of the migrations up to its version.
+pub const SQL_BASELINE_TYPE: &str = "SQL_BASELINE";
+/// History table type of a `RustMigration`.
+pub const RUST_TYPE: &str = "RUST";
+const BASELINE_DESCRIPTION: &str = "<< Baseline >>";
+
+/// How migrations are wrapped in transactions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TransactionMode {
+    /// Every migration runs in its own transaction.
+    #[default]
+    PerMigration,
+    /// All pending migrations run in a single transaction, either all of them
+    /// are applied or none.
+    All,
+    /// Migrations run without a transaction, needed for statements like
+    /// `CREATE INDEX CONCURRENTLY`. A failed migration is recorded as failed in
+    /// the history table and has to be fixed with `repair`.
+    None,
+}
+
+/// Builder for running migrations and the other operations on the history
+/// table.
+///
+/// ```ignore
+/// let report = Migrator::new()
+///     .location("migrations")
+///     .placeholder("schema", "app")
+///     .target("2.0")?
+///     .migrate(&pool)
+///     .await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct Migrator {
+    locations: Vec<String>,
+    sources: Vec<SqlFile>,
+    rust_migrations: Vec<RustMigrationRef>,
+    discovery: DiscoveryOptions,
+    placeholders: Placeholders,
+    history_table: String,
+    target: Option<MigrationVersion>,
+    out_of_order: bool,
+    transaction_mode: TransactionMode,
+    run_hooks: bool,
+    baseline_version: MigrationVersion,
+    observers: Observers,
+    session: SessionSettings,
+    lock_timeout_retries: u32,
+    lock_timeout_retry_interval: Duration,
+}
+
+impl Default for Migrator {
+    fn default() -> Self {
+        Self {
+            locations: vec![],
+            sources: vec![],
+            rust_migrations: vec![],
+            discovery: DiscoveryOptions::default(),
+            placeholders: Placeholders::new(),
+            history_table: DEFAULT_HISTORY_TABLE.to_string(),
+            target: None,
+            out_of_order: false,
+            transaction_mode: TransactionMode::default(),
+            run_hooks: true,
+            baseline_version: "1".parse().unwrap(),
+            observers: Observers::default(),
+            session: SessionSettings::default(),
+            lock_timeout_retries: 0,
+            lock_timeout_retry_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+impl Migrator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a directory, or a glob pattern matching directories, to read
+    /// migrations from.
+    pub fn location<P: Into<String>>(mut self, location: P) -> Self {
+        self.locations.push(location.into());
+        self
+    }
+
+    /// Adds directories, or glob patterns matching directories, to read
+    /// migrations from.
+    pub fn locations<P: Into<String>>(mut self, locations: Vec<P>) -> Self {
+        self.locations.extend(locations.into_iter().map(Into::into));
+        self
+    }
+
+    /// Adds migration files that are already loaded, like embedded migrations.
+    pub fn sources(mut self, files: Vec<SqlFile>) -> Self {
+        self.sources.extend(files);
+        self
+    }
+
+    /// Adds the migrations embedded with `embed_migrations!`.
+    pub fn embedded(self, migrations: EmbeddedMigrations) -> Self {
+        self.sources(migrations.files())
+    }
+
+    /// How the locations are scanned for migration files.
+    pub fn discovery(mut self, options: DiscoveryOptions) -> Self {
+        self.discovery = options;
+        self
+    }
+
+    /// Replaces the placeholders expanded in the migrations.
+    pub fn placeholders(mut self, placeholders: Placeholders) -> Self {
+        self.placeholders = placeholders;
+        self
+    }
+
+    /// Adds a placeholder expanded in the migrations as `${name}`.
+    pub fn placeholder<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Self {
+        self.placeholders.insert(name.into(), value.into());
+        self
+    }
+
+    /// The table, optionally schema qualified, used to keep track of the
+    /// applied migrations. Defaults to `_schema_history`.
+    pub fn history_table<T: Into<String>>(mut self, table: T) -> Self {
+        self.history_table = table.into();
+        self
+    }
+
+    /// Only migrate up to and including this version, when undoing, undo the
+    /// migrations above this version.
+    pub fn target(mut self, version: &str) -> Result<Self> {
+        self.target = Some(parse_version(version)?);
+        Ok(self)
+    }
+
+    /// Allow pending migrations with a version lower than the latest applied
+    /// migration to be applied, instead of failing.
+    pub fn out_of_order(mut self, out_of_order: bool) -> Self {
+        self.out_of_order = out_of_order;
+        self
+    }
+
+    pub fn transaction_mode(mut self, mode: TransactionMode) -> Self {
+        self.transaction_mode = mode;
+        self
+    }
+
+    /// Run the `beforeMigrate`, `beforeEachMigrate`, `afterEachMigrate`,
+    /// `afterMigrate` and `afterMigrateError` hooks. Enabled by default.
+    pub fn hooks(mut self, run_hooks: bool) -> Self {
+        self.run_hooks = run_hooks;
+        self
+    }
+
+    /// The version recorded by `baseline`. Defaults to `1`.
+    pub fn baseline_version(mut self, version: &str) -> Result<Self> {
+        self.baseline_version = parse_version(version)?;
+        Ok(self)
+    }
+
+    /// Session settings every migration runs with, set with `SET LOCAL` in
+    /// the migration transaction. Migrations can override them, see
+    /// `SessionSettings`.
+    pub fn session_settings(mut self, settings: SessionSettings) -> Self {
+        self.session = settings;
+        self
+    }
+
+    /// Retries a migration that gave up waiting for a lock, see
+    /// `lock_timeout`, up to `retries` times. Only migrations running in their
+    /// own transaction are retried.
+    pub fn lock_timeout_retries(mut self, retries: u32, interval: Duration) -> Self {
+        self.lock_timeout_retries = retries;
+        self.lock_timeout_retry_interval = interval;
+        self
+    }
+
+    /// Adds a migration written in Rust, applied with the migration files in
+    /// version order.
+    pub fn rust_migration(mut self, migration: Arc<dyn RustMigration>) -> Self {
+        self.rust_migrations.push(RustMigrationRef(migration));
+        self
+    }
+
+    /// Adds an observer notified when migrations start, are applied, fail or
+    /// are skipped.
+    pub fn observer(mut self, observer: Arc<dyn MigrationObserver>) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    /// Applies the pending migrations.
+    pub async fn migrate(&self, pool: &Pool) -> Result<MigrateReport> {
+        let span = info_span!("migrate", history_table = %self.history_table);
+        async {
+            let resolved = self.resolve()?;
+            let client = get_client(pool).await?;
+            self.locked(&client, self.migrate_locked(&client, &resolved))
+                .await
+        }
+        .instrument(span)
+        .await
+    }
+
+    async fn migrate_locked(&self, client: &Client, resolved: &Resolved) -> Result<MigrateReport> {
+        // The beforeMigrate hook runs before anything else so it can prepare
+        // the database, like creating the schema for the history table.
+        self.run_hook(client, resolved, "beforeMigrate").await?;
+
+        create_schema_history_if_needed(client, &self.history_table).await?;
+        let rows = get_schema_history_rows(client, &self.history_table).await?;
+        let history = History::new(&self.history_table, rows)?;
+        let plan = self.plan(resolved, &history);
+        if let Some(issue) = plan.issues.into_iter().next() {
+            let error: Error = issue.into();
+            error!(error = %error, "validation failed");
+            return Err(error);
+        }
+
+        for skipped in &plan.skipped {
+            info!(script = %skipped.script, state = ?skipped.state, "skipped migration");
+            self.observers.skipped(skipped);
+        }
+        if plan.pending.is_empty() {
+            info!("nothing to migrate");
+        }
+
+        let table = quote_table_name(&self.history_table);
+        for (installed_rank, file) in &plan.legacy_checksums {
+            client
+                .execute(
+                    &format!("UPDATE {table} SET checksum = $1 WHERE installed_rank = $2"),
+                    &[&file.checksum, installed_rank],
+                )
+                .await?;
+        }
+
+        for warning in &plan.warnings {
+            warn!("{warning}");
+        }
+        let mut report = MigrateReport {
+            applied: vec![],
+            skipped: plan.skipped,
+            warnings: plan.warnings,
+        };
+        match self
+            .apply(client, resolved, &plan.pending, &mut report)
+            .await
+        {
+            Ok(()) => {
+                self.run_hook(client, resolved, "afterMigrate").await?;
+                info!(applied = report.applied.len(), "migration completed");
+                Ok(report)
+            }
+            Err(e) => {
+                // Best effort, the migration error is the one worth reporting.
+                let _ = self.run_hook(client, resolved, "afterMigrateError").await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Lists every migration, applied or not, and its state.
+    pub async fn info(&self, pool: &Pool) -> Result<InfoReport> {
+        let resolved = self.resolve()?;
+        let client = get_client(pool).await?;
+        let history = self.read_history(&client).await?;
+        let plan = self.plan(&resolved, &history);
+
+        let mut migrations: Vec<MigrationInfo> = vec![];
+        if let Some(row) = &history.baseline {
+            migrations.push(MigrationInfo::from_row(row, MigrationState::Baseline));
+        }
+        for row in &history.failed {
+            migrations.push(MigrationInfo::from_row(row, MigrationState::Failed));
+        }
+        for file in &resolved.migrations {
+            let version = file.migration_version();
+            if let Some(row) = version.as_ref().and_then(|v| history.applied.get(v)) {
+                migrations.push(MigrationInfo::from_row(row, MigrationState::Applied));
+            } else if let Some(skipped) = plan.skipped.iter().find(|s| s.script == file.file_name) {
+                migrations.push(MigrationInfo::from_file(file, skipped.state));
+            } else if plan.pending.iter().any(|p| p.file_name == file.file_name) {
+                migrations.push(MigrationInfo::from_file(file, MigrationState::Pending));
+            } else {
+                migrations.push(MigrationInfo::from_file(file, MigrationState::Ignored));
+            }
+        }
+        for file in &resolved.baselines {
+            if plan.pending.iter().any(|p| p.file_name == file.file_name) {
+                migrations.push(MigrationInfo::from_file(file, MigrationState::Pending));
+            }
+        }
+        for issue in &plan.issues {
+            if let ValidationIssue::Missing(missing) = issue
+                && let Some(row) = history
+                    .applied
+                    .values()
+                    .find(|r| r.script == missing.script)
+            {
+                migrations.push(MigrationInfo::from_row(row, MigrationState::Missing));
+            }
+        }
+        migrations.sort_by(|a, b| compare_versions(&a.version, &b.version));
+
+        Ok(InfoReport { migrations })
+    }
+
+    /// Checks the applied migrations against the migration files without
+    /// changing anything.
+    pub async fn validate(&self, pool: &Pool) -> Result<ValidateReport> {
+        let resolved = self.resolve()?;
+        let client = get_client(pool).await?;
+        let history = self.read_history(&client).await?;
+        let plan = self.plan(&resolved, &history);
+        Ok(ValidateReport {
+            issues: plan.issues,
+            pending: plan.pending.iter().map(|f| f.file_name.clone()).collect(),
+        })
+    }
+
+    /// Checks the migrations up to the target version for operations that
+    /// block the tables they change, without connecting to the database.
+    pub fn lint(&self, options: &LintOptions) -> Result<LintReport> {
+        let resolved = self.resolve()?;
+        let files: Vec<&SqlInnerFile> = resolved
+            .migrations
+            .iter()
+            .filter(|file| match (&self.target, file.migration_version()) {
+                (Some(target), Some(version)) => &version <= target,
+                _ => true,
+            })
+            .collect();
+        lint_files(&files, options)
+    }
+
+    /// Checks the migrations pending in the database, see `lint`.
+    pub async fn lint_pending(&self, pool: &Pool, options: &LintOptions) -> Result<LintReport> {
+        let resolved = self.resolve()?;
+        let client = get_client(pool).await?;
+        let history = self.read_history(&client).await?;
+        let plan = self.plan(&resolved, &history);
+        lint_files(&plan.pending, options)
+    }
+
+    /// Runs the undo migration of the latest applied migration, or of every
+    /// applied migration above the target version when one is set.
+    pub async fn undo(&self, pool: &Pool) -> Result<UndoReport> {
+        let span = info_span!("undo", history_table = %self.history_table);
+        async {
+            let resolved = self.resolve()?;
+            let client = get_client(pool).await?;
+            self.locked(&client, self.undo_locked(&client, &resolved))
+                .await
+        }
+        .instrument(span)
+        .await
+    }
+
+    async fn undo_locked(&self, client: &Client, resolved: &Resolved) -> Result<UndoReport> {
+        let history = self.read_history(client).await?;
+        if let Some(row) = history.failed.first() {
+            return Err(FailedMigrationError {
+                version: row.version.clone(),
+                script: row.script.clone(),
+            }
+            .into());
+        }
+
+        let mut applied: Vec<&SchemaHistoryRow> = history.applied.values().collect();
+        applied.sort_by_key(|row| row.installed_rank);
+        let to_undo: Vec<&SchemaHistoryRow> = match &self.target {
+            Some(target) => applied
+                .into_iter()
+                .rev()
+                .filter(|row| row_version(row).is_some_and(|v| &v > target))
+                .collect(),
+            None => applied.into_iter().rev().take(1).collect(),
+        };
+
+        let mut report = UndoReport { undone: vec![] };
+        for row in to_undo {
+            let version = row_version(row).unwrap();
+            let Some(file) = resolved
+                .undo
+                .iter()
+                .find(|f| f.migration_version().as_ref() == Some(&version))
+            else {
+                return Err(MissingUndoError {
+                    version: version.to_string(),
+                    script: row.script.clone(),
+                }
+                .into());
+            };
+            let undone = self
+                .observed(file, self.execute(client, resolved, file, "U"))
+                .await?;
+            report.undone.push(undone);
+        }
+        Ok(report)
+    }
+
+    /// Removes failed migrations from the history, realigns the checksums and
+    /// descriptions of the applied migrations with the files and marks
+    /// applied migrations that no longer have a file as deleted.
+    pub async fn repair(&self, pool: &Pool) -> Result<RepairReport> {
+        let span = info_span!("repair", history_table = %self.history_table);
+        async {
+            let resolved = self.resolve()?;
+            let client = get_client(pool).await?;
+            self.locked(&client, self.repair_locked(&client, &resolved))
+                .await
+        }
+        .instrument(span)
+        .await
+    }
+
+    async fn repair_locked(&self, client: &Client, resolved: &Resolved) -> Result<RepairReport> {
+        let history = self.read_history(client).await?;
+        let table = quote_table_name(&self.history_table);
+        let mut report = RepairReport::default();
+
+        client.batch_execute("BEGIN;").await?;
+        let result: Result<()> = async {
+            for row in &history.failed {
+                client
+                    .execute(
+                        &format!("DELETE FROM {table} WHERE installed_rank = $1"),
+                        &[&row.installed_rank],
+                    )
+                    .await?;
+                report.removed_failed.push(row.script.clone());
+            }
+
+            let files: HashMap<MigrationVersion, &SqlInnerFile> = resolved
+                .migrations
+                .iter()
+                .filter_map(|f| f.migration_version().map(|v| (v, f)))
+                .collect();
+            for (version, row) in &history.applied {
+                match files.get(version) {
+                    Some(file)
+                        if !checksum_matches(row, file)
+                            || row.checksum == Some(LEGACY_CHECKSUM)
+                            || row.description != file.description =>
+                    {
+                        client
+                            .execute(
+                                &format!(
+                                    "UPDATE {table} SET checksum = $1, description = $2 WHERE installed_rank = $3"
+                                ),
+                                &[&file.checksum, &file.description, &row.installed_rank],
+                            )
+                            .await?;
+                        report.realigned.push(file.file_name.clone());
+                    }
+                    Some(_) => {}
+                    None => {
+                        insert_schema_history_row(
+                            client,
+                            &self.history_table,
+                            NewSchemaHistoryRow {
+                                version: row.version.as_deref(),
+                                description: &row.description,
+                                r#type: DELETE_TYPE,
+                                script: &row.script,
+                                checksum: row.checksum,
+                                execution_time: 0,
+                                success: true,
+                            },
+                        )
+                        .await?;
+                        report.deleted.push(row.script.clone());
+                    }
+                }
+            }
+            Ok(())
+        }
+        .await;
+
+        match result {
+            Ok(()) => {
+                client.batch_execute("COMMIT;").await?;
+                Ok(report)
+            }
+            Err(e) => {
+                client.batch_execute("ROLLBACK;").await?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Marks an existing database as migrated up to and including the
+    /// baseline version, migrations at or below it are never applied.
+    pub async fn baseline(&self, pool: &Pool) -> Result<BaselineReport> {
+        let span = info_span!("baseline", history_table = %self.history_table);
+        async {
+            let client = get_client(pool).await?;
+            self.locked(&client, self.baseline_locked(&client)).await
+        }
+        .instrument(span)
+        .await
+    }
+
+    async fn baseline_locked(&self, client: &Client) -> Result<BaselineReport> {
+        create_schema_history_if_needed(client, &self.history_table).await?;
+        let rows = get_schema_history_rows(client, &self.history_table).await?;
+        if !rows.is_empty() {
+            return Err(HistoryNotEmptyError {
+                table: self.history_table.clone(),
+                operation: "baseline".to_string(),
+            }
+            .into());
+        }
+        let version = self.baseline_version.to_string();
+        insert_schema_history_row(
+            client,
+            &self.history_table,
+            NewSchemaHistoryRow {
+                version: Some(&version),
+                description: BASELINE_DESCRIPTION,
+                r#type: BASELINE_TYPE,
+                script: BASELINE_DESCRIPTION,
+                checksum: None,
+                execution_time: 0,
+                success: true,
+            },
+        )
+        .await?;
+        Ok(BaselineReport { version })
+    }
+
+    /// Squashes the migrations up to `up_to` into a baseline migration, a
+    /// `B` file with the schema they create. Empty databases get the baseline
+    /// instead of the migrations, databases migrated before are left alone.
+    ///
+    /// The schema is dumped with `pg_dump` from a scratch database created
+    /// on the server of `url`.
+    pub async fn squash(
+        &self,
+        url: &str,
+        up_to: &str,
+        options: &SquashOptions,
+    ) -> Result<SquashReport> {
+        let version = parse_version(up_to)?;
+        let resolved = self.resolve()?;
+        let squashed: Vec<&SqlInnerFile> = resolved
+            .migrations
+            .iter()
+            .chain(&resolved.undo)
+            .filter(|file| file.migration_version().is_some_and(|v| v <= version))
+            .collect();
+        if !squashed.iter().any(|file| file.prefix == "V") {
+            return Err(
+                ConfigError::new(format!("No migrations up to version {up_to} to squash")).into(),
+            );
+        }
+        let dir = options
+            .dir
+            .as_ref()
+            .or(self.locations.first())
+            .ok_or_else(|| ConfigError::new("No directory to write the baseline migration to"))?;
+        let description = options.description.as_deref().unwrap_or("Baseline");
+        let path = Path::new(dir)
+            .join(format!("B{up_to}__{}.sql", slugify(description)))
+            .to_string_lossy()
+            .to_string();
+        if Path::new(&path).exists() {
+            return Err(
+                ConfigError::new(format!("The baseline migration {path} already exists")).into(),
+            );
+        }
+
+        let mut scratch = self.clone();
+        scratch.target = Some(version);
+        let dump = squash::scratch_schema(url, &scratch, options, &self.history_table).await?;
+        fs::File::create_new(&path)
+            .and_then(|mut file| file.write_all(squash::baseline_script(&dump, up_to).as_bytes()))
+            .map_err(|e| write_error(&path, e))?;
+        info!(baseline = %path, squashed = squashed.len(), "squashed migrations");
+
+        let mut report = SquashReport {
+            baseline: path,
+            squashed: squashed.iter().map(|file| file.file_name.clone()).collect(),
+            archived: vec![],
+        };
+        if let Some(archive) = &options.archive {
+            // Rust migrations are compiled in, there is no file to move.
+            for file in squashed.iter().filter(|file| file.rust.is_none()) {
+                report
+                    .archived
+                    .push(squash::archive(&file.file_path, &file.file_name, archive)?);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Copies the history of a database migrated by Flyway, its history
+    /// table `from`, into the empty history table. The rows keep their rank
+    /// and checksum, and nothing is copied unless the migrations validate
+    /// against them.
+    pub async fn import_history(&self, pool: &Pool, from: &str) -> Result<HistoryCopyReport> {
+        let span = info_span!("import_history", history_table = %self.history_table, from);
+        async {
+            let resolved = self.resolve()?;
+            let client = get_client(pool).await?;
+            self.locked(&client, async {
+                let rows = self.read_copied_history(&client, from).await?;
+                let rows = rows
+                    .into_iter()
+                    .map(|row| flyway::import_row(from, row))
+                    .collect::<Result<Vec<_>>>()?;
+                self.validate_copy(&resolved, &rows)?;
+                create_schema_history_if_needed(&client, &self.history_table).await?;
+                self.copy_history(
+                    &client,
+                    rows,
+                    from,
+                    &self.history_table,
+                    "import the history",
+                )
+                .await
+            })
+            .await
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Copies the history table into Flyway's history table `to`, created
+    /// when missing, so Flyway can take over the database. Nothing is copied
+    /// unless the migrations validate against the history.
+    pub async fn export_history(&self, pool: &Pool, to: &str) -> Result<HistoryCopyReport> {
+        let span = info_span!("export_history", history_table = %self.history_table, to);
+        async {
+            let resolved = self.resolve()?;
+            let client = get_client(pool).await?;
+            self.locked(&client, async {
+                let rows = self
+                    .read_copied_history(&client, &self.history_table)
+                    .await?;
+                self.validate_copy(&resolved, &rows)?;
+                // Flyway validates the files with its own checksums.
+                let checksums: HashMap<(&str, MigrationVersion), i32> = resolved
+                    .migrations
+                    .iter()
+                    .chain(&resolved.undo)
+                    .chain(&resolved.baselines)
+                    .filter(|file| file.rust.is_none())
+                    .filter_map(|file| {
+                        let r#type = if file.prefix == "U" {
+                            "U"
+                        } else {
+                            history_type(file)
+                        };
+                        Some((
+                            (r#type, file.migration_version()?),
+                            flyway::checksum(&file.content),
+                        ))
+                    })
+                    .collect();
+                let rows = rows
+                    .into_iter()
+                    .map(|mut row| {
+                        if let Some(version) = row_version(&row)
+                            && let Some(checksum) = checksums.get(&(row.r#type.as_str(), version))
+                        {
+                            row.checksum = Some(*checksum);
+                        }
+                        flyway::export_row(&self.history_table, row)
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                flyway::create_history_table_if_needed(&client, to).await?;
+                self.copy_history(&client, rows, &self.history_table, to, "export the history")
+                    .await
+            })
+            .await
+        }
+        .instrument(span)
+        .await
+    }
+
+    async fn read_copied_history(
+        &self,
+        client: &Client,
+        table: &str,
+    ) -> Result<Vec<SchemaHistoryRow>> {
+        if !schema_history_exists(client, table).await? {
+            return Err(
+                ConfigError::new(format!("The history table {table} does not exist")).into(),
+            );
+        }
+        get_schema_history_rows(client, table).await
+    }
+
+    /// Refuses to copy a history the migrations do not validate against.
+    fn validate_copy(&self, resolved: &Resolved, rows: &[SchemaHistoryRow]) -> Result<()> {
+        let history = History::new(&self.history_table, rows.to_vec())?;
+        match self.plan(resolved, &history).issues.into_iter().next() {
+            Some(issue) => Err(issue.into()),
+            None => Ok(()),
+        }
+    }
+
+    async fn copy_history(
+        &self,
+        client: &Client,
+        rows: Vec<SchemaHistoryRow>,
+        from: &str,
+        to: &str,
+        operation: &str,
+    ) -> Result<HistoryCopyReport> {
+        client.batch_execute("BEGIN;").await?;
+        let result: Result<()> = async {
+            if !get_schema_history_rows(client, to).await?.is_empty() {
+                return Err(HistoryNotEmptyError {
+                    table: to.to_string(),
+                    operation: operation.to_string(),
+                }
+                .into());
+            }
+            insert_history_rows(client, to, &rows).await
+        }
+        .await;
+        match result {
+            Ok(()) => client.batch_execute("COMMIT;").await?,
+            Err(e) => {
+                client.batch_execute("ROLLBACK;").await?;
+                return Err(e);
+            }
+        }
+        info!(from, to, rows = rows.len(), "copied the history table");
+        Ok(HistoryCopyReport {
+            from: from.to_string(),
+            to: to.to_string(),
+            copied: rows.into_iter().map(|row| row.script).collect(),
+        })
+    }
+
+    fn resolve(&self) -> Result<Resolved> {
+        let mut files = read_sql_files(self.locations.clone(), &self.discovery)?;
+        files.extend(self.sources.iter().cloned());
+
+        let hooks: HashMap<String, SqlFile> = files
+            .iter()
+            .filter(|f| is_hook(&f.file_name))
+            .map(|f| (f.file_name.trim_end_matches(".sql").to_string(), f.clone()))
+            .collect();
+        let mut resolved = Resolved {
+            migrations: vec![],
+            undo: vec![],
+            baselines: vec![],
+            hooks,
+        };
+        let mut files = parse_sql_files(files)?;
+        if !self.rust_migrations.is_empty() {
+            for migration in &self.rust_migrations {
+                files.push(migration.resolve()?);
+            }
+            verify_unique_versions(&files)?;
+        }
+        for file in sort_sql_files(files) {
+            match file.prefix.as_str() {
+                "V" => resolved.migrations.push(file),
+                "U" => resolved.undo.push(file),
+                "B" => resolved.baselines.push(file),
+                prefix => unreachable!("`{prefix}` files are rejected by validate_file_name"),
+            }
+        }
+        Ok(resolved)
+    }
+
+    /// Reads the history without creating the history table.
+    async fn read_history(&self, client: &Client) -> Result<History> {
+        if !schema_history_exists(client, &self.history_table).await? {
+            return History::new(&self.history_table, vec![]);
+        }
+        let rows = get_schema_history_rows(client, &self.history_table).await?;
+        History::new(&self.history_table, rows)
+    }
+
+    fn plan<'a>(&self, resolved: &'a Resolved, history: &History) -> Plan<'a> {
+        let mut plan = Plan {
+            legacy_checksums: vec![],
+            pending: vec![],
+            skipped: vec![],
+            issues: vec![],
+            warnings: vec![],
+        };
+        for row in &history.failed {
+            plan.issues
+                .push(ValidationIssue::Failed(FailedMigrationError {
+                    version: row.version.clone(),
+                    script: row.script.clone(),
+                }));
+        }
+
+        let mut baseline = history.baseline.as_ref().and_then(row_version);
+        // An empty database gets the latest baseline migration instead of
+        // the migrations it squashes.
+        if history.is_empty()
+            && let Some(file) = resolved.baselines.iter().rev().find(|file| {
+                self.target
+                    .as_ref()
+                    .is_none_or(|t| file.migration_version().is_some_and(|v| &v <= t))
+            })
+        {
+            baseline = file.migration_version();
+            plan.pending.push(file);
+        }
+        // Databases migrated up to the squash before it can have applied
+        // migrations whose files are gone.
+        let squash_point = resolved
+            .baselines
+            .iter()
+            .filter(|file| file.migration_version().is_some())
+            .max_by_key(|file| file.migration_version());
+        let squashed = squash_point.and_then(|file| file.migration_version());
+        let latest = history.applied.keys().max().cloned();
+        let reached = latest
+            .clone()
+            .max(history.baseline.as_ref().and_then(row_version));
+        let past_squash = squashed
+            .as_ref()
+            .is_some_and(|s| reached.as_ref().is_some_and(|r| r >= s));
+        // A database behind the squash point needs the squashed migrations,
+        // without them the versions up to the baseline would never be applied.
+        if let (Some(file), Some(squashed)) = (squash_point, &squashed)
+            && !history.is_empty()
+            && !past_squash
+            && !resolved.migrations.iter().any(|m| {
+                m.migration_version()
+                    .is_some_and(|v| &v <= squashed && reached.as_ref().is_none_or(|r| &v > r))
+            })
+        {
+            plan.issues
+                .push(ValidationIssue::Squashed(SquashedMigrationsError {
+                    version: reached
+                        .as_ref()
+                        .map(ToString::to_string)
+                        .unwrap_or_default(),
+                    baseline: file.file_name.clone(),
+                }));
+        }
+        let mut found: Vec<&MigrationVersion> = vec![];
+        for file in &resolved.migrations {
+            let Some(version) = file.migration_version() else {
+                continue;
+            };
+            if let Some(row) = history.applied.get(&version) {
+                found.push(history.applied.get_key_value(&version).unwrap().0);
+                if row.checksum == Some(LEGACY_CHECKSUM) {
+                    plan.legacy_checksums.push((row.installed_rank, file));
+                }
+                if !checksum_matches(row, file) {
+                    plan.issues
+                        .push(ValidationIssue::ChecksumMismatch(ChecksumMismatchError {
+                            file_name: file.file_name.clone(),
+                            file_checksum: file.checksum,
+                            applied_checksum: row.checksum.unwrap_or_default(),
+                        }));
+                }
+            } else if baseline.as_ref().is_some_and(|b| &version <= b) {
+                plan.skip(file, MigrationState::BelowBaseline);
+            } else if self.target.as_ref().is_some_and(|t| &version > t) {
+                plan.skip(file, MigrationState::AboveTarget);
+            } else if latest.as_ref().is_some_and(|l| &version < l) {
+                if self.out_of_order {
+                    plan.warnings.push(format!(
+                        "Migration {} is applied out of order, the latest applied version is {}",
+                        file.file_name,
+                        latest.as_ref().unwrap()
+                    ));
+                    plan.pending.push(file);
+                } else {
+                    plan.skip(file, MigrationState::Ignored);
+                    plan.issues
+                        .push(ValidationIssue::OutOfOrder(OutOfOrderError {
+                            version: version.to_string(),
+                            script: file.file_name.clone(),
+                            latest_version: latest.as_ref().unwrap().to_string(),
+                        }));
+                }
+            } else {
+                plan.pending.push(file);
+            }
+        }
+
+        for (version, row) in &history.applied {
+            let squashed_away = past_squash && squashed.as_ref().is_some_and(|s| version <= s);
+            if !found.contains(&version) && !squashed_away {
+                plan.issues
+                    .push(ValidationIssue::Missing(MissingMigrationError {
+                        version: version.to_string(),
+                        script: row.script.clone(),
+                    }));
+            }
+        }
+        plan
+    }
+
+    async fn apply(
+        &self,
+        client: &Client,
+        resolved: &Resolved,
+        pending: &[&SqlInnerFile],
+        report: &mut MigrateReport,
+    ) -> Result<()> {
+        if self.transaction_mode != TransactionMode::All {
+            for file in pending {
+                let applied = self
+                    .observed(
+                        file,
+                        self.execute(client, resolved, file, history_type(file)),
+                    )
+                    .await?;
+                self.observers.applied(&applied);
+                report.applied.push(applied);
+            }
+            return Ok(());
+        }
+
+        client.batch_execute("BEGIN;").await?;
+        for file in pending {
+            let execution = self.execute_in_transaction(client, resolved, file, history_type(file));
+            match self.observed(file, execution).await {
+                Ok(applied) => report.applied.push(applied),
+                Err(e) => {
+                    client.batch_execute("ROLLBACK;").await?;
+                    report.applied.clear();
+                    return Err(e);
+                }
+            }
+        }
+        client.batch_execute("COMMIT;").await?;
+        // Only committed migrations are applied.
+        for applied in &report.applied {
+            self.observers.applied(applied);
+        }
+        Ok(())
+    }
+
+    /// Runs a single migration, in its own transaction unless transactions
+    /// are disabled, and records it in the history table.
+    async fn execute(
+        &self,
+        client: &Client,
+        resolved: &Resolved,
+        file: &SqlInnerFile,
+        r#type: &str,
+    ) -> Result<i32> {
+        if self.transaction_mode == TransactionMode::None {
+            let started = Instant::now();
+            let result = self.run_migration(client, resolved, file).await;
+            let execution_time = started.elapsed().as_millis() as i32;
+            self.record(client, file, r#type, execution_time, result.is_ok())
+                .await?;
+            return result.map(|_| execution_time);
+        }
+
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            client.batch_execute("BEGIN;").await?;
+            match self
+                .execute_in_transaction(client, resolved, file, r#type)
+                .await
+            {
+                Ok(execution_time) => {
+                    client.batch_execute("COMMIT;").await?;
+                    return Ok(execution_time);
+                }
+                Err(e) => {
+                    client.batch_execute("ROLLBACK;").await?;
+                    if e.sql_state() != Some(SqlState::LOCK_NOT_AVAILABLE.code())
+                        || attempt > self.lock_timeout_retries
+                    {
+                        return Err(e);
+                    }
+                    warn!(
+                        attempt,
+                        error = %e,
+                        "migration gave up waiting for a lock, retrying"
+                    );
+                    tokio::time::sleep(self.lock_timeout_retry_interval).await;
+                }
+            }
+        }
+    }
+
+    async fn execute_in_transaction(
+        &self,
+        client: &Client,
+        resolved: &Resolved,
+        file: &SqlInnerFile,
+        r#type: &str,
+    ) -> Result<i32> {
+        let started = Instant::now();
+        self.run_migration(client, resolved, file).await?;
+        let execution_time = started.elapsed().as_millis() as i32;
+        self.record(client, file, r#type, execution_time, true)
+            .await?;
+        Ok(execution_time)
+    }
+
+    async fn run_migration(
+        &self,
+        client: &Client,
+        resolved: &Resolved,
+        file: &SqlInnerFile,
+    ) -> Result<()> {
+        let execution_error =
+            |e| ExecutionError::new(file.file_name.clone(), file.version.clone(), e);
+        let settings = self.session.merge(&file.settings);
+        if self.transaction_mode == TransactionMode::None {
+            session::set_session(client, &settings)
+                .await
+                .map_err(execution_error)?;
+            let result = self.run_file(client, resolved, file).await;
+            session::reset_session(client, &settings)
+                .await
+                .map_err(execution_error)?;
+            return result;
+        }
+        // Migrations sharing a transaction must not inherit the overrides of
+        // the migrations before them.
+        session::set_local(
+            client,
+            &settings,
+            self.transaction_mode == TransactionMode::All,
+        )
+        .await
+        .map_err(execution_error)?;
+        self.run_file(client, resolved, file).await?;
+        session::reset_local(client, &settings)
+            .await
+            .map_err(execution_error)?;
+        Ok(())
+    }
+
+    async fn run_file(
+        &self,
+        client: &Client,
+        resolved: &Resolved,
+        file: &SqlInnerFile,
+    ) -> Result<()> {
+        self.run_hook(client, resolved, "beforeEachMigrate").await?;
+        if let Some(migration) = &file.rust {
+            migration.up(client, file).await?;
+        } else {
+            let content = fill_template(&file.content, &self.placeholders)?;
+            client.batch_execute(&content).await.map_err(|e| {
+                ExecutionError::new(file.file_name.clone(), file.version.clone(), e)
+            })?;
+        }
+        self.run_hook(client, resolved, "afterEachMigrate").await?;
+        Ok(())
+    }
+
+    async fn record(
+        &self,
+        client: &Client,
+        file: &SqlInnerFile,
+        r#type: &str,
+        execution_time: i32,
+        success: bool,
+    ) -> Result<()> {
+        debug!(
+            script = %file.file_name,
+            r#type,
+            success,
+            "recording migration in the history table"
+        );
+        insert_schema_history_row(
+            client,
+            &self.history_table,
+            NewSchemaHistoryRow {
+                version: file.version.as_deref(),
+                description: &file.description,
+                r#type,
+                script: &file.file_name,
+                checksum: Some(file.checksum),
+                execution_time,
+                success,
+            },
+        )
+        .await
+    }
+
+    /// Runs a migration in its own span and notifies the observers that it
+    /// started or failed, the caller notifies them once it is committed.
+    async fn observed<F>(&self, file: &SqlInnerFile, execution: F) -> Result<AppliedMigration>
+    where
+        F: Future<Output = Result<i32>>,
+    {
+        let span = info_span!(
+            "migration",
+            version = file.version.as_deref().unwrap_or_default(),
+            script = %file.file_name,
+        );
+        let event = MigrationEvent {
+            version: file.version.clone(),
+            description: file.description.clone(),
+            script: file.file_name.clone(),
+        };
+        self.observers.started(&event);
+        match execution.instrument(span.clone()).await {
+            Ok(execution_time) => {
+                span.in_scope(|| info!(execution_time, "applied migration"));
+                Ok(AppliedMigration::new(file, execution_time))
+            }
+            Err(e) => {
+                span.in_scope(|| error!(error = %e, "migration failed"));
+                self.observers.failed(&event, &e);
+                Err(e)
+            }
+        }
+    }
+
+    /// Runs the operation while holding a session level advisory lock, so
+    /// two pgmt processes never change the same history table at once.
+    async fn locked<F, T>(&self, client: &Client, operation: F) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        let key = self.lock_key();
+        let lock_error = |source| LockError { key, source };
+        let locked: bool = client
+            .query_one("SELECT pg_try_advisory_lock($1)", &[&key])
+            .await
+            .map_err(lock_error)?
+            .get(0);
+        if !locked {
+            warn!(
+                lock = key,
+                "waiting for another migration to release the lock"
+            );
+            let started = Instant::now();
+            client
+                .execute("SELECT pg_advisory_lock($1)", &[&key])
+                .await
+                .map_err(lock_error)?;
+            info!(
+                lock = key,
+                waited_ms = started.elapsed().as_millis() as u64,
+                "acquired the migration lock"
+            );
+        }
+
+        let result = operation.await;
+
+        // The session outlives this call in the pool, so the lock must be
+        // released even when the operation failed.
+        if let Err(e) = client
+            .execute("SELECT pg_advisory_unlock($1)", &[&key])
+            .await
+        {
+            warn!(lock = key, error = %e, "unable to release the migration lock");
+        }
+        result
+    }
+
+    fn lock_key(&self) -> i64 {
+        let mut hasher = Crc32Hasher::new();
+        hasher.update(b"pgmt:");
+        hasher.update(self.history_table.as_bytes());
+        hasher.finalize() as i64
+    }
+
+    async fn run_hook(&self, client: &Client, resolved: &Resolved, name: &str) -> Result<()> {
+        if !self.run_hooks {
+            return Ok(());
+        }
+        if let Some(hook) = resolved.hooks.get(name) {
+            debug!(hook = name, "running hook");
+            let content = fill_template(&hook.content, &self.placeholders)?;
+            client
+                .batch_execute(&content)
+                .await
+                .map_err(|e| ExecutionError::new(hook.file_name.clone(), None, e))?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_version(version: &str) -> Result<MigrationVersion> {
+    version
+        .parse()
+        .map_err(|e: String| ConfigError::new(format!("Invalid version: {e}")).into())
+}
+
+fn history_type(file: &SqlInnerFile) -> &'static str {
+    if file.prefix == "B" {
+        SQL_BASELINE_TYPE
+    } else if file.rust.is_some() {
+        RUST_TYPE
+    } else {
+        "V"
+    }
+}
+
+/// The checksum pgmt recorded for every migration before it checksummed
+/// their content, `crc32("foo bar baz")`.
+const LEGACY_CHECKSUM: i32 = -228401567;
+
+/// Rows imported from Flyway keep the checksum Flyway computed, rows with the
+/// legacy checksum match any content.
+fn checksum_matches(row: &SchemaHistoryRow, file: &SqlInnerFile) -> bool {
+    row.checksum == Some(file.checksum)
+        || row.checksum == Some(LEGACY_CHECKSUM)
+        || (file.rust.is_none() && row.checksum == Some(flyway::checksum(&file.content)))
+}
+
+fn row_version(row: &SchemaHistoryRow) -> Option<MigrationVersion> {
+    row.version.as_ref().and_then(|v| v.parse().ok())
+}
+
+fn compare_versions(a: &Option<String>, b: &Option<String>) -> std::cmp::Ordering {
+    let parse = |v: &Option<String>| v.as_ref().and_then(|v| v.parse::<MigrationVersion>().ok());
+    parse(a).cmp(&parse(b))
+}
+
+struct Resolved {
+    migrations: Vec<SqlInnerFile>,
+    undo: Vec<SqlInnerFile>,
+    /// Baseline migrations, `B` files, squashing the migrations up to their
+    /// version.
+    baselines: Vec<SqlInnerFile>,
+    hooks: HashMap<String, SqlFile>,
+}
+
+/// The state of the database according to the history table.
+struct History {
+    /// The applied migrations that have not been undone or deleted.
+    applied: BTreeMap<MigrationVersion, SchemaHistoryRow>,
+    baseline: Option<SchemaHistoryRow>,
+    failed: Vec<SchemaHistoryRow>,
+}
+
+impl History {
+    fn new(table: &str, rows: Vec<SchemaHistoryRow>) -> Result<Self> {
+        let mut history = History {
+            applied: BTreeMap::new(),
+            baseline: None,
+            failed: vec![],
+        };
+        for row in rows {
+            // Rows without a version, like repeatable migrations, do not
+            // change which versions are applied.
+            let Some(version) = &row.version else {
+                continue;
+            };
+            let version: MigrationVersion =
+                version.parse().map_err(|reason| HistoryCorruptionError {
+                    table: table.to_string(),
+                    installed_rank: row.installed_rank,
+                    reason,
+                })?;
+            match row.r#type.as_str() {
+                _ if !row.success => history.failed.push(row),
+                "V" | RUST_TYPE => {
+                    history.applied.insert(version, row);
+                }
+                "U" | DELETE_TYPE => {
+                    history.applied.remove(&version);
+                }
+                BASELINE_TYPE | SQL_BASELINE_TYPE => history.baseline = Some(row),
+                _ => {}
+            }
+        }
+        Ok(history)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.applied.is_empty() && self.baseline.is_none() && self.failed.is_empty()
+    }
+}
+
+struct Plan<'a> {
+    pending: Vec<&'a SqlInnerFile>,
+    /// Applied migrations recorded with `LEGACY_CHECKSUM`, by their
+    /// `installed_rank`, their checksum is written on the next migrate.
+    legacy_checksums: Vec<(i32, &'a SqlInnerFile)>,
+    skipped: Vec<SkippedMigration>,
+    issues: Vec<ValidationIssue>,
+    warnings: Vec<String>,
+}
+
+impl Plan<'_> {
+    fn skip(&mut self, file: &SqlInnerFile, state: MigrationState) {
+        self.skipped.push(SkippedMigration {
+            version: file.version.clone(),
+            script: file.file_name.clone(),
+            state,
+        });
+    }
+}
+
+/// The state of a migration as reported by `info`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// The version is above the target version.
+    AboveTarget,
+    /// The version is at or below the baseline version.
+    BelowBaseline,
+    /// The version is below the latest applied migration and out of order
+    /// migrations are not allowed.
+    Ignored,
+    /// The migration is applied but its file is gone.
+    Missing,
+    Failed,
+    Baseline,
+}
+
+/// Something wrong with the applied migrations, found by `validate`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ValidationIssue {
+    ChecksumMismatch(ChecksumMismatchError),
+    Missing(MissingMigrationError),
+    OutOfOrder(OutOfOrderError),
+    Failed(FailedMigrationError),
+    Squashed(SquashedMigrationsError),
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationIssue::ChecksumMismatch(e) => write!(f, "{e}"),
+            ValidationIssue::Missing(e) => write!(f, "{e}"),
+            ValidationIssue::OutOfOrder(e) => write!(f, "{e}"),
+            ValidationIssue::Failed(e) => write!(f, "{e}"),
+            ValidationIssue::Squashed(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<ValidationIssue> for Error {
+    fn from(issue: ValidationIssue) -> Self {
+        match issue {
+            ValidationIssue::ChecksumMismatch(e) => e.into(),
+            ValidationIssue::Missing(e) => e.into(),
+            ValidationIssue::OutOfOrder(e) => e.into(),
+            ValidationIssue::Failed(e) => e.into(),
+            ValidationIssue::Squashed(e) => e.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AppliedMigration {
+    pub version: Option<String>,
+    pub description: String,
+    pub script: String,
+    /// Time in milliseconds to execute the migration
+    pub execution_time: i32,
+}
+
+impl AppliedMigration {
+    fn new(file: &SqlInnerFile, execution_time: i32) -> Self {
+        Self {
+            version: file.version.clone(),
+            description: file.description.clone(),
+            script: file.file_name.clone(),
+            execution_time,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SkippedMigration {
+    pub version: Option<String>,
+    pub script: String,
+    pub state: MigrationState,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MigrateReport {
+    pub applied: Vec<AppliedMigration>,
+    pub skipped: Vec<SkippedMigration>,
+    /// Things worth knowing that did not stop the migration, like migrations
+    /// applied out of order.
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MigrationInfo {
+    pub version: Option<String>,
+    pub description: String,
+    pub script: String,
+    pub r#type: String,
+    pub state: MigrationState,
+    pub checksum: Option<i32>,
+    pub installed_on: Option<DateTime<Utc>>,
+    pub execution_time: Option<i32>,
+}
+
+impl MigrationInfo {
+    fn from_row(row: &SchemaHistoryRow, state: MigrationState) -> Self {
+        Self {
+            version: row.version.clone(),
+            description: row.description.clone(),
+            script: row.script.clone(),
+            r#type: row.r#type.clone(),
+            state,
+            checksum: row.checksum,
+            installed_on: Some(row.installed_on),
+            execution_time: Some(row.execution_time),
+        }
+    }
+
+    fn from_file(file: &SqlInnerFile, state: MigrationState) -> Self {
+        Self {
+            version: file.version.clone(),
+            description: file.description.clone(),
+            script: file.file_name.clone(),
+            r#type: match file.rust {
+                Some(_) => RUST_TYPE.to_string(),
+                None => file.prefix.clone(),
+            },
+            state,
+            checksum: Some(file.checksum),
+            installed_on: None,
+            execution_time: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InfoReport {
+    pub migrations: Vec<MigrationInfo>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ValidateReport {
+    pub issues: Vec<ValidationIssue>,
+    /// Script names of the migrations that would be applied by `migrate`.
+    pub pending: Vec<String>,
+}
+
+impl ValidateReport {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UndoReport {
+    pub undone: Vec<AppliedMigration>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RepairReport {
+    /// Script names of the failed migrations removed from the history.
+    pub removed_failed: Vec<String>,
+    /// Script names of the migrations whose checksum and description were
+    /// updated to match the file.
+    pub realigned: Vec<String>,
+    /// Script names of the applied migrations without a file that were
+    /// marked as deleted.
+    pub deleted: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BaselineReport {
+    pub version: String,
+}
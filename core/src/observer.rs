@@ -0,0 +1,65 @@
+use crate::Error;
+use crate::migrator::{AppliedMigration, SkippedMigration};
+use serde::Serialize;
+use std::fmt;
+use std::sync::Arc;
+
+/// Receives progress from a `Migrator` so applications embedding pgmt can
+/// route it into their own logging or UI.
+///
+/// Every callback has an empty default implementation, implement only the
+/// ones you need.
+pub trait MigrationObserver: Send + Sync {
+    /// A migration, or an undo migration, is about to run.
+    fn started(&self, _migration: &MigrationEvent) {}
+
+    /// A migration was applied and recorded in the history table, with
+    /// `TransactionMode::All` once the transaction is committed.
+    fn applied(&self, _migration: &AppliedMigration) {}
+
+    /// A migration failed, for migrations running in a transaction nothing
+    /// of it was applied.
+    fn failed(&self, _migration: &MigrationEvent, _error: &Error) {}
+
+    /// A migration was not applied, see `SkippedMigration::state` for why.
+    fn skipped(&self, _migration: &SkippedMigration) {}
+}
+
+/// The migration a `MigrationObserver` callback is about.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MigrationEvent {
+    pub version: Option<String>,
+    pub description: String,
+    pub script: String,
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Observers(Vec<Arc<dyn MigrationObserver>>);
+
+impl Observers {
+    pub(crate) fn push(&mut self, observer: Arc<dyn MigrationObserver>) {
+        self.0.push(observer);
+    }
+
+    pub(crate) fn started(&self, migration: &MigrationEvent) {
+        self.0.iter().for_each(|o| o.started(migration));
+    }
+
+    pub(crate) fn applied(&self, migration: &AppliedMigration) {
+        self.0.iter().for_each(|o| o.applied(migration));
+    }
+
+    pub(crate) fn failed(&self, migration: &MigrationEvent, error: &Error) {
+        self.0.iter().for_each(|o| o.failed(migration, error));
+    }
+
+    pub(crate) fn skipped(&self, migration: &SkippedMigration) {
+        self.0.iter().for_each(|o| o.skipped(migration));
+    }
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Observers({})", self.0.len())
+    }
+}
@@ -0,0 +1,153 @@
+use crate::error::Result;
+use deadpool_postgres::{Pool, PoolError};
+use rand::Rng;
+use std::time::Duration;
+use tokio_postgres::error::SqlState;
+use tracing::{info, warn};
+
+/// How to connect to a database that may still be starting, like in
+/// docker-compose or Kubernetes where pgmt can start before Postgres.
+#[derive(Debug, Clone)]
+pub struct ConnectOptions {
+    /// Attempts after the first one, `None` keeps trying until the server is
+    /// reachable.
+    pub retries: Option<u32>,
+    /// Delay before the first retry, it doubles after every attempt up to
+    /// `max_retry_interval` and a random part of it is taken off as jitter.
+    pub retry_interval: Duration,
+    pub max_retry_interval: Duration,
+    /// Gives up on a single attempt after this long, replaces the
+    /// `connect_timeout` of the connection string.
+    pub timeout: Option<Duration>,
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        Self {
+            retries: Some(0),
+            retry_interval: Duration::from_secs(1),
+            max_retry_interval: Duration::from_secs(30),
+            timeout: None,
+        }
+    }
+}
+
+impl ConnectOptions {
+    fn delay(&self, attempt: u32) -> Duration {
+        let delay = self
+            .retry_interval
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_retry_interval);
+        rand::rng().random_range(delay / 2..=delay)
+    }
+}
+
+/// Returns once a connection to the database could be made, retrying
+/// failures that may go away while the server starts.
+pub(crate) async fn wait_until_ready(pool: &Pool, options: &ConnectOptions) -> Result<()> {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let error = match pool.get().await {
+            Ok(_) => {
+                if attempt > 1 {
+                    info!(attempt, "Connected to the database");
+                }
+                return Ok(());
+            }
+            Err(error) => error,
+        };
+        if !is_transient(&error) || options.retries.is_some_and(|retries| attempt > retries) {
+            return Err(error.into());
+        }
+        let delay = options.delay(attempt);
+        warn!(
+            attempt,
+            delay_ms = delay.as_millis() as u64,
+            error = %error,
+            "Unable to connect to the database, retrying"
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Only failures that go away once the server accepts connections are
+/// retried, wrong credentials, a missing database or a failed TLS handshake
+/// will not get better by trying again.
+fn is_transient(error: &PoolError) -> bool {
+    match error {
+        PoolError::Backend(e) => match e.code() {
+            Some(code) => *code == SqlState::CANNOT_CONNECT_NOW,
+            None => e.is_closed() || io_error(e).is_some_and(is_transient_io),
+        },
+        PoolError::Timeout(_) => true,
+        _ => false,
+    }
+}
+
+fn io_error(error: &tokio_postgres::Error) -> Option<&std::io::Error> {
+    std::error::Error::source(error)?.downcast_ref()
+}
+
+/// The server is not listening yet, or went away while it was starting.
+fn is_transient_io(error: &std::io::Error) -> bool {
+    use std::io::ErrorKind::*;
+    matches!(
+        error.kind(),
+        ConnectionRefused
+            | ConnectionReset
+            | ConnectionAborted
+            | NotConnected
+            | BrokenPipe
+            | UnexpectedEof
+            | TimedOut
+            | HostUnreachable
+            | NetworkUnreachable
+            // The Unix socket of a server that has not started.
+            | NotFound
+    )
+}
+
+#[test]
+fn retries_back_off_exponentially() {
+    let options = ConnectOptions {
+        retry_interval: Duration::from_millis(100),
+        max_retry_interval: Duration::from_millis(1000),
+        ..ConnectOptions::default()
+    };
+    for (attempt, max) in [
+        (1, 100),
+        (2, 200),
+        (3, 400),
+        (4, 800),
+        (5, 1000),
+        (40, 1000),
+    ] {
+        let delay = options.delay(attempt);
+        assert!(delay <= Duration::from_millis(max), "{attempt}: {delay:?}");
+        assert!(
+            delay >= Duration::from_millis(max / 2),
+            "{attempt}: {delay:?}"
+        );
+    }
+}
+
+#[test]
+fn only_connection_failures_are_transient() {
+    use std::io::{Error, ErrorKind};
+    assert!(is_transient_io(&Error::from(ErrorKind::ConnectionRefused)));
+    assert!(is_transient_io(&Error::from(ErrorKind::TimedOut)));
+    assert!(!is_transient_io(&Error::from(ErrorKind::InvalidData)));
+    assert!(!is_transient_io(&Error::other("invalid peer certificate")));
+
+    assert!(!is_transient(&PoolError::Closed));
+    let refused = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(tokio_postgres::connect(
+            "host=127.0.0.1 port=1",
+            tokio_postgres::NoTls,
+        ))
+        .map(|_| ())
+        .unwrap_err();
+    assert!(is_transient(&PoolError::Backend(refused)));
+}
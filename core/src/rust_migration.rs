@@ -0,0 +1,139 @@
+use crate::error::{BoxError, ConfigError, ExecutionError, Result, RustMigrationError};
+use crate::generate::slugify;
+use crate::{MigrationVersion, SessionSettings, SqlInnerFile};
+use async_trait::async_trait;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// A migration written in Rust, for data migrations SQL can not express,
+/// like re-encrypting a column. Rust migrations are applied with the SQL
+/// migrations in version order and recorded in the history table with the
+/// type `RUST`.
+///
+/// ```ignore
+/// struct ReencryptTokens;
+///
+/// #[pgmt_core::async_trait]
+/// impl RustMigration for ReencryptTokens {
+///     fn version(&self) -> &str {
+///         "1.3"
+///     }
+///
+///     fn description(&self) -> &str {
+///         "Reencrypt tokens"
+///     }
+///
+///     async fn up(&self, transaction: &Transaction<'_>) -> Result<(), BoxError> {
+///         for row in transaction.query("SELECT id, token FROM account", &[]).await? {
+///             // ...
+///         }
+///         Ok(())
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait RustMigration: Send + Sync {
+    /// Version like the version of a `V` file, it must not be used by a
+    /// file.
+    fn version(&self) -> &str;
+
+    fn description(&self) -> &str;
+
+    /// Recorded in the history table, change it when the migration changes
+    /// and `validate` reports the databases that applied the old one.
+    fn checksum(&self) -> i32 {
+        0
+    }
+
+    async fn up(&self, transaction: &Transaction<'_>) -> std::result::Result<(), BoxError>;
+}
+
+/// The connection a Rust migration runs on. It is in the transaction of the
+/// migration, or of every migration with `TransactionMode::All`, and in no
+/// transaction with `TransactionMode::None`.
+///
+/// Tests with transaction isolation get one in the transaction they are
+/// rolled back with.
+pub struct Transaction<'a> {
+    client: &'a tokio_postgres::Client,
+}
+
+impl<'a> Transaction<'a> {
+    pub(crate) fn new(client: &'a tokio_postgres::Client) -> Self {
+        Transaction { client }
+    }
+}
+
+impl Deref for Transaction<'_> {
+    type Target = tokio_postgres::Client;
+
+    fn deref(&self) -> &Self::Target {
+        self.client
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct RustMigrationRef(pub(crate) Arc<dyn RustMigration>);
+
+impl RustMigrationRef {
+    /// Turns the Rust migration into a migration like the ones read from
+    /// files, named `V<version>__<Description>`.
+    pub(crate) fn resolve(&self) -> Result<SqlInnerFile> {
+        let migration = &self.0;
+        let version = migration
+            .version()
+            .parse::<MigrationVersion>()
+            .map_err(|e| {
+                ConfigError::new(format!(
+                    "Invalid version of the Rust migration `{}`: {e}",
+                    migration.description()
+                ))
+            })?;
+        let file_name = format!(
+            "V{}__{}",
+            migration.version(),
+            slugify(migration.description())
+        );
+        Ok(SqlInnerFile {
+            content: String::new(),
+            checksum: migration.checksum(),
+            file_path: file_name.clone(),
+            file_name,
+            prefix: "V".to_string(),
+            version: Some(version.to_string()),
+            description: migration.description().to_string(),
+            settings: SessionSettings::default(),
+            rust: Some(self.clone()),
+        })
+    }
+
+    pub(crate) async fn up(
+        &self,
+        client: &tokio_postgres::Client,
+        file: &SqlInnerFile,
+    ) -> Result<()> {
+        self.0.up(&Transaction { client }).await.map_err(|source| {
+            match source.downcast::<tokio_postgres::Error>() {
+                // Database errors keep their SQLSTATE, so lock timeouts are
+                // retried like they are for SQL migrations.
+                Ok(e) => {
+                    ExecutionError::new(file.file_name.clone(), file.version.clone(), *e).into()
+                }
+                Err(source) => RustMigrationError {
+                    script: file.file_name.clone(),
+                    version: file.version.clone(),
+                    message: source.to_string(),
+                    source,
+                }
+                .into(),
+            }
+        })
+    }
+}
+
+impl fmt::Debug for RustMigrationRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RustMigration({})", self.0.version())
+    }
+}
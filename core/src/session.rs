@@ -0,0 +1,184 @@
+use deadpool_postgres::Client;
+
+/// Session settings a migration runs with, instead of whatever defaults the
+/// pooled connection has. A blocked `ALTER TABLE` with a `lock_timeout` gives
+/// up instead of queueing every other query on the table behind it.
+///
+/// Migrations override the settings of the run with comments at the top of
+/// the file.
+///
+/// ```sql
+/// -- pgmt:lock_timeout = 5s
+/// -- pgmt:role = app_owner
+/// ALTER TABLE account ADD COLUMN email TEXT;
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionSettings {
+    pub lock_timeout: Option<String>,
+    pub statement_timeout: Option<String>,
+    pub role: Option<String>,
+    pub search_path: Option<String>,
+    pub application_name: Option<String>,
+}
+
+impl SessionSettings {
+    /// Returns these settings with the ones set in `overrides` replaced.
+    pub fn merge(&self, overrides: &SessionSettings) -> SessionSettings {
+        let pick = |a: &Option<String>, b: &Option<String>| b.clone().or_else(|| a.clone());
+        SessionSettings {
+            lock_timeout: pick(&self.lock_timeout, &overrides.lock_timeout),
+            statement_timeout: pick(&self.statement_timeout, &overrides.statement_timeout),
+            role: pick(&self.role, &overrides.role),
+            search_path: pick(&self.search_path, &overrides.search_path),
+            application_name: pick(&self.application_name, &overrides.application_name),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries().iter().all(|(_, value)| value.is_none())
+    }
+
+    fn entries(&self) -> [(&'static str, Option<&str>); 5] {
+        [
+            ("lock_timeout", self.lock_timeout.as_deref()),
+            ("statement_timeout", self.statement_timeout.as_deref()),
+            ("role", self.role.as_deref()),
+            ("search_path", self.search_path.as_deref()),
+            ("application_name", self.application_name.as_deref()),
+        ]
+    }
+
+    /// Reads the `-- pgmt:name = value` lines from the comments at the top of
+    /// a migration.
+    pub(crate) fn from_front_matter(content: &str) -> Result<Self, String> {
+        let mut settings = Self::default();
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() {
+                continue;
+            }
+            let Some(comment) = line.strip_prefix("--") else {
+                break;
+            };
+            let Some(setting) = comment.trim_start().strip_prefix("pgmt:") else {
+                continue;
+            };
+            let (name, value) = setting.split_once('=').ok_or_else(|| {
+                format!("invalid setting `{line}`, expected `-- pgmt:name = value`")
+            })?;
+            let value = Some(value.trim().to_string());
+            match name.trim() {
+                "lock_timeout" => settings.lock_timeout = value,
+                "statement_timeout" => settings.statement_timeout = value,
+                "role" => settings.role = value,
+                "search_path" => settings.search_path = value,
+                "application_name" => settings.application_name = value,
+                name => return Err(format!("unknown setting `{name}`")),
+            }
+        }
+        Ok(settings)
+    }
+}
+
+/// Applies the settings to the current transaction, `reset` resets the
+/// settings that are not set to their session defaults, which matters when
+/// several migrations share a transaction.
+pub(crate) async fn set_local(
+    client: &Client,
+    settings: &SessionSettings,
+    reset: bool,
+) -> Result<(), tokio_postgres::Error> {
+    for (name, value) in settings.entries() {
+        match value {
+            Some(value) => {
+                client
+                    .execute("SELECT set_config($1, $2, true)", &[&name, &value])
+                    .await?;
+            }
+            None if reset => {
+                client
+                    .batch_execute(&format!("SET LOCAL {name} TO DEFAULT"))
+                    .await?;
+            }
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/// Undoes `set_local` before the migration is recorded, the history table is
+/// written as the connecting role with its settings.
+pub(crate) async fn reset_local(
+    client: &Client,
+    settings: &SessionSettings,
+) -> Result<(), tokio_postgres::Error> {
+    for (name, value) in settings.entries() {
+        if value.is_some() {
+            client
+                .batch_execute(&format!("SET LOCAL {name} TO DEFAULT"))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Applies the settings to the session, for migrations that run without a
+/// transaction.
+pub(crate) async fn set_session(
+    client: &Client,
+    settings: &SessionSettings,
+) -> Result<(), tokio_postgres::Error> {
+    for (name, value) in settings.entries() {
+        if let Some(value) = value {
+            client
+                .execute("SELECT set_config($1, $2, false)", &[&name, &value])
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Undoes `set_session`, the connection goes back to the pool afterwards.
+pub(crate) async fn reset_session(
+    client: &Client,
+    settings: &SessionSettings,
+) -> Result<(), tokio_postgres::Error> {
+    for (name, value) in settings.entries() {
+        if value.is_some() {
+            client.batch_execute(&format!("RESET {name}")).await?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn reads_settings_from_the_front_matter() {
+    let settings = SessionSettings::from_front_matter(
+        "-- Adds the email column\n--pgmt:lock_timeout = 5s\n\n-- pgmt:search_path=app, public\nALTER TABLE t ADD COLUMN e TEXT;\n-- pgmt:role = ignored\n",
+    )
+    .unwrap();
+    assert_eq!(
+        settings,
+        SessionSettings {
+            lock_timeout: Some("5s".to_string()),
+            search_path: Some("app, public".to_string()),
+            ..SessionSettings::default()
+        }
+    );
+    assert!(SessionSettings::from_front_matter("-- pgmt:lock_timeout").is_err());
+    assert!(SessionSettings::from_front_matter("-- pgmt:work_mem = 1GB").is_err());
+
+    let run = SessionSettings {
+        lock_timeout: Some("1s".to_string()),
+        role: Some("app".to_string()),
+        ..SessionSettings::default()
+    };
+    assert_eq!(
+        run.merge(&settings),
+        SessionSettings {
+            lock_timeout: Some("5s".to_string()),
+            role: Some("app".to_string()),
+            search_path: Some("app, public".to_string()),
+            ..SessionSettings::default()
+        }
+    );
+}
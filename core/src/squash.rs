@@ -0,0 +1,128 @@
+use crate::error::{DumpError, Result};
+use crate::generate::write_error;
+use crate::retry::{self, ConnectOptions};
+use crate::{Migrator, conninfo, create_pool, generate_temp_db_name, new_cfg, setup, teardown};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use tokio::process::Command;
+
+/// How `Migrator::squash` creates the baseline migration.
+#[derive(Debug, Clone, Default)]
+pub struct SquashOptions {
+    /// Directory the baseline migration is written to, defaults to the first
+    /// migration location.
+    pub dir: Option<String>,
+    /// Description of the baseline migration, defaults to `Baseline`.
+    pub description: Option<String>,
+    /// Moves the squashed migrations to this directory, outside the
+    /// migration locations, instead of leaving them next to the baseline.
+    pub archive: Option<String>,
+    /// The `pg_dump` to run, found on the `PATH` by default.
+    pub pg_dump: Option<String>,
+    /// How to connect to the server the scratch database is created on.
+    pub connect: ConnectOptions,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SquashReport {
+    /// Path of the baseline migration.
+    pub baseline: String,
+    /// The migrations it replaces.
+    pub squashed: Vec<String>,
+    /// Where the squashed migrations were moved to.
+    pub archived: Vec<String>,
+}
+
+/// Migrates a scratch database on the server of `url` and dumps its schema.
+pub(crate) async fn scratch_schema(
+    url: &str,
+    migrator: &Migrator,
+    options: &SquashOptions,
+    history_table: &str,
+) -> Result<String> {
+    let mut cfg = new_cfg(url.to_string())?;
+    if let Some(timeout) = options.connect.timeout {
+        cfg.config.connect_timeout(timeout);
+    }
+    retry::wait_until_ready(&create_pool(&cfg).await?, &options.connect).await?;
+    let db_name = generate_temp_db_name();
+    let pool = setup(cfg, db_name.clone()).await?;
+    let result = async {
+        migrator.migrate(&pool).await?;
+        dump_schema(
+            options.pg_dump.as_deref().unwrap_or("pg_dump"),
+            &conninfo::with_dbname(url, &db_name)?,
+            history_table,
+        )
+        .await
+    }
+    .await;
+    pool.close();
+    teardown(url.to_string(), &db_name).await?;
+    result
+}
+
+async fn dump_schema(pg_dump: &str, url: &str, history_table: &str) -> Result<String> {
+    let output = Command::new(pg_dump)
+        .args(["--schema-only", "--no-owner", "--no-privileges"])
+        .args(["--exclude-table", history_table])
+        // The sequence of the SERIAL installed_rank column.
+        .arg("--exclude-table")
+        .arg(format!("{history_table}_installed_rank_seq"))
+        .args(["--dbname", url])
+        .output()
+        .await
+        .map_err(|e| DumpError {
+            message: format!("unable to run {pg_dump}: {e}"),
+            source: Some(Box::new(e)),
+        })?;
+    if !output.status.success() {
+        return Err(DumpError {
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            source: None,
+        }
+        .into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Turns a dump into a migration. The settings `pg_dump` makes for the whole
+/// session only last for the migration, and `psql` commands are left out.
+/// The empty `search_path` is left out too, the dump qualifies every name
+/// but the history table is written in the same transaction.
+pub(crate) fn baseline_script(dump: &str, version: &str) -> String {
+    let mut script =
+        format!("-- Baseline of the migrations up to version {version}, created by pgmt squash.\n");
+    for line in dump.lines() {
+        if line.starts_with('\\') || line.starts_with("SELECT pg_catalog.set_config('search_path'")
+        {
+            continue;
+        }
+        if let Some(setting) = line.strip_prefix("SET ") {
+            script.push_str("SET LOCAL ");
+            script.push_str(setting);
+        } else {
+            script.push_str(line);
+        }
+        script.push('\n');
+    }
+    script
+}
+
+/// Moves a squashed migration out of the migration locations.
+pub(crate) fn archive(file_path: &str, file_name: &str, dir: &str) -> Result<String> {
+    fs::create_dir_all(dir).map_err(|e| write_error(dir, e))?;
+    let target = Path::new(dir).join(file_name).to_string_lossy().to_string();
+    fs::rename(file_path, &target).map_err(|e| write_error(&target, e))?;
+    Ok(target)
+}
+
+#[test]
+fn dumps_become_migrations() {
+    let dump = "--\n-- PostgreSQL database dump\n--\n\\restrict abc\nSET statement_timeout = 0;\nSELECT pg_catalog.set_config('search_path', '', false);\nCREATE TABLE public.t (\n    id integer\n);\n\\unrestrict abc\n";
+    assert_eq!(
+        baseline_script(dump, "1.2"),
+        "-- Baseline of the migrations up to version 1.2, created by pgmt squash.\n--\n-- PostgreSQL database dump\n--\nSET LOCAL statement_timeout = 0;\nCREATE TABLE public.t (\n    id integer\n);\n"
+    );
+}
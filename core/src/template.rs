@@ -1,4 +1,4 @@
-use crate::error::{Result, MissingVariableTemplateError };
+use crate::error::{MissingVariableTemplateError, Result};
 use regex::Regex;
 use std::collections::HashMap;
 
@@ -19,7 +19,7 @@ pub fn fill_template(template: &str, vars: &HashMap<String, String>) -> Result<S
         if let Some(val) = vars.get(variable_name) {
             result.push_str(val);
         } else {
-            return Err(MissingVariableTemplateError  {
+            return Err(MissingVariableTemplateError {
                 name: variable_name.to_string(),
             }
             .into());
@@ -50,8 +50,8 @@ fn test_it() {
     assert!(res.is_err());
     if let Err(error) = res {
         assert_eq!(
-            error.to_string(), 
-            r#"MissingVariableTemplateError(MissingVariableTemplateError { name: "undefinde_variable" })"#.to_string()
+            error.to_string(),
+            "Missing value for the placeholder ${undefinde_variable}".to_string()
         );
     }
 }
@@ -0,0 +1,192 @@
+use crate::error::Result;
+use crate::{Placeholders, Pool, conninfo, teardown, test_template};
+use chrono::{Local, NaiveDateTime, TimeZone};
+use serde::Serialize;
+use std::time::Duration;
+
+/// Test databases are named `pgmt_test_{timestamp}_{letters}_{test}`.
+pub(crate) const TEST_DB_PREFIX: &str = "pgmt_test_";
+pub(crate) const TIMESTAMP_FORMAT: &str = "%Y_%m_%d_%H_%M_%S";
+/// Length of the formatted timestamp, `2024_01_05_10_20_30`.
+const TIMESTAMP_LENGTH: usize = 19;
+/// Postgres truncates longer names.
+const MAX_NAME_LENGTH: usize = 63;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TestDbGcReport {
+    /// Names of the dropped test databases and templates.
+    pub dropped: Vec<String>,
+}
+
+/// The test database given to `test_migration_with_context` and
+/// `#[pgmt::test]` tests.
+#[derive(Debug, Clone)]
+pub struct TestContext {
+    pub pool: Pool,
+    /// Connection URL of the test database, for tools other than the pool.
+    pub url: String,
+    pub db_name: String,
+    /// The placeholders the test database was migrated with.
+    pub placeholders: Placeholders,
+}
+
+/// When to keep the test database instead of dropping it, `PGMT_KEEP_DB`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum KeepDb {
+    Never,
+    OnFailure,
+    Always,
+}
+
+impl KeepDb {
+    fn from_env() -> std::result::Result<Self, String> {
+        match std::env::var("PGMT_KEEP_DB").as_deref() {
+            Err(_) | Ok("") | Ok("never") => Ok(KeepDb::Never),
+            Ok("on-failure") => Ok(KeepDb::OnFailure),
+            Ok("always") => Ok(KeepDb::Always),
+            Ok(value) => Err(format!(
+                "PGMT_KEEP_DB must be never, on-failure or always, not `{value}`"
+            )),
+        }
+    }
+}
+
+/// Drops the test database when it goes out of scope, also when the test
+/// panics, unless `PGMT_KEEP_DB` keeps it.
+pub(crate) struct DatabaseGuard {
+    db_url: String,
+    db_name: String,
+    keep: KeepDb,
+}
+
+impl DatabaseGuard {
+    pub(crate) fn new(db_url: &str, db_name: &str) -> Self {
+        let mut guard = DatabaseGuard {
+            db_url: db_url.to_string(),
+            db_name: db_name.to_string(),
+            keep: KeepDb::Never,
+        };
+        // Panicking drops the database.
+        guard.keep = KeepDb::from_env().unwrap_or_else(|error| panic!("{error}"));
+        guard
+    }
+}
+
+impl Drop for DatabaseGuard {
+    fn drop(&mut self) {
+        let failed = std::thread::panicking();
+        if self.keep == KeepDb::Always || (self.keep == KeepDb::OnFailure && failed) {
+            let url = conninfo::database_url(&self.db_url, &self.db_name)
+                .unwrap_or_else(|_| self.db_name.clone());
+            println!("Kept the test database {url}");
+            return;
+        }
+        let db_url = self.db_url.clone();
+        let db_name = self.db_name.clone();
+        // The test's runtime can not be blocked on, the database is dropped
+        // from a runtime of its own.
+        let dropped = std::thread::spawn(move || {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("Unable to start a runtime to drop the test database")
+                .block_on(teardown(db_url, &db_name))
+        })
+        .join();
+        let error = match dropped {
+            Ok(Ok(())) => return,
+            Ok(Err(error)) => error.to_string(),
+            Err(_) => "the teardown panicked".to_string(),
+        };
+        let message = format!("Unable to drop the test database {}: {error}", self.db_name);
+        if failed {
+            eprintln!("{message}");
+        } else {
+            panic!("{message}");
+        }
+    }
+}
+
+/// Appends the test, as much of it as fits, to the name of its database.
+pub(crate) fn with_test_name(db_name: String, test: &str) -> String {
+    // Only the function of `module::test`.
+    let test = test.rsplit("::").next().unwrap_or(test);
+    let test: String = test
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .take(MAX_NAME_LENGTH.saturating_sub(db_name.len() + 1))
+        .collect();
+    if test.is_empty() {
+        return db_name;
+    }
+    format!("{db_name}_{test}")
+}
+
+/// When the test database was created, from the timestamp in its name.
+fn created_at(db_name: &str) -> Option<chrono::DateTime<Local>> {
+    let rest = db_name.strip_prefix(TEST_DB_PREFIX)?;
+    // `{timestamp}_{letters}` and maybe `_{test}`.
+    let timestamp = rest.get(..TIMESTAMP_LENGTH)?;
+    let created = NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok()?;
+    Local.from_local_datetime(&created).earliest()
+}
+
+/// Drops the test databases created more than `older_than` ago, left behind
+/// by test runs that were killed, and the templates built before then.
+pub async fn drop_test_databases(pool: &Pool, older_than: Duration) -> Result<TestDbGcReport> {
+    let client = pool.get().await?;
+    let cutoff = Local::now() - older_than;
+    let names: Vec<String> = client
+        .query(
+            "SELECT datname FROM pg_database WHERE starts_with(datname, $1) ORDER BY datname",
+            &[&TEST_DB_PREFIX],
+        )
+        .await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+    let mut dropped = vec![];
+    for name in names {
+        if created_at(&name).is_some_and(|created| created < cutoff) {
+            client
+                .batch_execute(&format!("DROP DATABASE IF EXISTS {name} WITH (FORCE)"))
+                .await?;
+            dropped.push(name);
+        }
+    }
+    dropped.extend(test_template::drop_stale_templates(&client, cutoff).await?);
+    Ok(TestDbGcReport { dropped })
+}
+
+#[test]
+fn reads_the_creation_time_from_the_name() {
+    let created = created_at("pgmt_test_2024_01_05_10_20_30_abcdef").unwrap();
+    assert_eq!(
+        created.format("%Y-%m-%d %H:%M:%S").to_string(),
+        "2024-01-05 10:20:30"
+    );
+    assert_eq!(
+        created_at("pgmt_test_2024_01_05_10_20_30_abcdef_creates_accounts"),
+        Some(created)
+    );
+    assert_eq!(created_at("pgmt_test_2024_01_05_abcdef"), None);
+    assert_eq!(created_at("pgmt_template_0badf00d"), None);
+}
+
+#[test]
+fn test_names_fit_in_the_database_name() {
+    let db_name = "pgmt_test_2024_01_05_10_20_30_abcdef".to_string();
+    assert_eq!(
+        with_test_name(db_name.clone(), "accounts::Creates-Accounts"),
+        "pgmt_test_2024_01_05_10_20_30_abcdef_creates_accounts"
+    );
+    let long = with_test_name(db_name.clone(), &"a".repeat(100));
+    assert_eq!(long.len(), MAX_NAME_LENGTH);
+    assert_eq!(with_test_name(db_name.clone(), ""), db_name);
+}
@@ -0,0 +1,292 @@
+use crate::conninfo::ConnectionSettings;
+use crate::error::Result;
+use crate::fixture::{Fixture, load_fixtures};
+use crate::test_database::{TEST_DB_PREFIX, with_test_name};
+use crate::{Placeholders, Pool, SqlFile, create_pool, get_client, migrate, temp_db_name};
+use chrono::{DateTime, Local};
+use crc32fast::Hasher as Crc32Hasher;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use tokio::runtime::Runtime;
+use tokio::sync::Mutex;
+
+/// Serializes building templates across test binaries.
+const TEMPLATE_LOCK: i64 = 0x7067_6d74_5445_4d50;
+pub(crate) const TEMPLATE_PREFIX: &str = "pgmt_template_";
+/// Shared databases are named like the database of a test named `shared`.
+const SHARED_TEST_NAME: &str = "shared";
+
+/// Name of the template database migrated with the files and placeholders,
+/// with the fixtures loaded, it changes when any of them change.
+pub(crate) fn template_name(
+    files: &[SqlFile],
+    placeholders: &Placeholders,
+    fixtures: &[Fixture],
+) -> String {
+    let mut files: Vec<&SqlFile> = files.iter().collect();
+    files.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+    let mut placeholders: Vec<(&String, &String)> = placeholders.iter().collect();
+    placeholders.sort();
+
+    let mut hasher = Crc32Hasher::new();
+    for file in files {
+        hasher.update(file.file_name.as_bytes());
+        hasher.update(&[0]);
+        hasher.update(file.content.as_bytes());
+        hasher.update(&[0]);
+    }
+    for (name, value) in placeholders {
+        hasher.update(name.as_bytes());
+        hasher.update(&[0]);
+        hasher.update(value.as_bytes());
+        hasher.update(&[0]);
+    }
+    // In order, later fixtures may use the rows of earlier ones.
+    for fixture in fixtures {
+        hasher.update(fixture.path.as_bytes());
+        hasher.update(&[0]);
+        hasher.update(fixture.content.as_bytes());
+        hasher.update(&[0]);
+    }
+    format!("{TEMPLATE_PREFIX}{:08x}", hasher.finalize())
+}
+
+/// Creates the database `db_name` as a copy of the template migrated with the
+/// files and loaded with the fixtures, migrating the template first unless an
+/// earlier test, or test run, already did.
+pub(crate) async fn create_from_template(
+    cfg: &ConnectionSettings,
+    db_name: &str,
+    files: Vec<SqlFile>,
+    placeholders: Placeholders,
+    fixtures: &[Fixture],
+) -> Result<()> {
+    let template = template_name(&files, &placeholders, fixtures);
+    let client = get_client(&create_pool(cfg).await?).await?;
+    // Created holding the lock, `drop_stale_templates` does not drop the
+    // template in between.
+    with_template_lock(&client, async || {
+        create_template(cfg, &client, &template, files, placeholders, fixtures).await?;
+        client
+            .batch_execute(&format!("CREATE DATABASE {db_name} TEMPLATE {template}"))
+            .await?;
+        Ok(())
+    })
+    .await
+}
+
+/// The shared databases of this process, by their template.
+static SHARED: Mutex<BTreeMap<String, Pool>> = Mutex::const_new(BTreeMap::new());
+
+/// Runs the connections to the shared databases, they outlive the runtime of
+/// the test that opened them.
+fn shared_runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("pgmt-shared-db")
+            .enable_all()
+            .build()
+            .expect("Unable to start the runtime of the shared test databases")
+    })
+}
+
+/// A connection to the database shared by the tests of this process with the
+/// same files, placeholders and fixtures, a copy of their template. The tests
+/// roll back their changes, it is kept like the template.
+pub(crate) async fn shared_client(
+    cfg: ConnectionSettings,
+    files: Vec<SqlFile>,
+    placeholders: Placeholders,
+    fixtures: Vec<Fixture>,
+) -> Result<deadpool_postgres::Client> {
+    let connect = shared_runtime().spawn(async move {
+        let template = template_name(&files, &placeholders, &fixtures);
+        let mut shared = SHARED.lock().await;
+        let pool = match shared.get(&template) {
+            Some(pool) => pool.clone(),
+            None => {
+                let pool = create_shared(cfg, &template, files, placeholders, &fixtures).await?;
+                shared.insert(template, pool.clone());
+                pool
+            }
+        };
+        drop(shared);
+        get_client(&pool).await
+    });
+    connect
+        .await
+        .unwrap_or_else(|error| std::panic::resume_unwind(error.into_panic()))
+}
+
+async fn create_shared(
+    mut cfg: ConnectionSettings,
+    template: &str,
+    files: Vec<SqlFile>,
+    placeholders: Placeholders,
+    fixtures: &[Fixture],
+) -> Result<Pool> {
+    let client = get_client(&create_pool(&cfg).await?).await?;
+    let shared = with_test_name(temp_db_name(), SHARED_TEST_NAME);
+    with_template_lock(&client, async || {
+        create_template(&cfg, &client, template, files, placeholders, fixtures).await?;
+        drop_unused_shared(&client).await?;
+        client
+            .batch_execute(&format!("CREATE DATABASE {shared} TEMPLATE {template}"))
+            .await?;
+        cfg.config.dbname(&shared);
+        let pool = create_pool(&cfg).await?;
+        // The pool keeps the connection until the process exits, the database
+        // is in use for `drop_unused_shared` as soon as the lock is released.
+        drop(get_client(&pool).await?);
+        Ok(pool)
+    })
+    .await
+}
+
+/// Drops the shared databases nobody is connected to, left behind by the
+/// processes that have exited.
+async fn drop_unused_shared(client: &deadpool_postgres::Client) -> Result<()> {
+    let pattern = format!("^{TEST_DB_PREFIX}[0-9_]+_[a-z]+_{SHARED_TEST_NAME}$");
+    let names = client
+        .query(
+            "SELECT datname FROM pg_database d WHERE datname ~ $1 \
+             AND NOT EXISTS (SELECT FROM pg_stat_activity a WHERE a.datname = d.datname)",
+            &[&pattern],
+        )
+        .await?;
+    for row in names {
+        let name: String = row.get(0);
+        client
+            .batch_execute(&format!("DROP DATABASE IF EXISTS {name}"))
+            .await?;
+    }
+    Ok(())
+}
+
+/// Runs `f` holding the lock that serializes creating templates.
+async fn with_template_lock<T>(
+    client: &deadpool_postgres::Client,
+    f: impl AsyncFnOnce() -> Result<T>,
+) -> Result<T> {
+    client
+        .execute("SELECT pg_advisory_lock($1)", &[&TEMPLATE_LOCK])
+        .await?;
+    let result = f().await;
+    client
+        .execute("SELECT pg_advisory_unlock($1)", &[&TEMPLATE_LOCK])
+        .await?;
+    result
+}
+
+async fn database_exists(client: &deadpool_postgres::Client, name: &str) -> Result<bool> {
+    Ok(client
+        .query_opt("SELECT FROM pg_database WHERE datname = $1", &[&name])
+        .await?
+        .is_some())
+}
+
+async fn create_template(
+    cfg: &ConnectionSettings,
+    client: &deadpool_postgres::Client,
+    template: &str,
+    files: Vec<SqlFile>,
+    placeholders: Placeholders,
+    fixtures: &[Fixture],
+) -> Result<()> {
+    if database_exists(client, template).await? {
+        return Ok(());
+    }
+    // Migrated under another name, a test run killed halfway leaves no
+    // half migrated template behind.
+    let building = format!("{template}_building");
+    for sql in [
+        format!("DROP DATABASE IF EXISTS {building} WITH (FORCE)"),
+        format!("CREATE DATABASE {building}"),
+    ] {
+        client.batch_execute(&sql).await?;
+    }
+    let mut template_cfg = cfg.clone();
+    template_cfg.config.dbname(&building);
+    let pool = create_pool(&template_cfg).await?;
+    migrate(&pool, files, placeholders.clone()).await?;
+    load_fixtures(&pool, fixtures, &placeholders).await?;
+    pool.close();
+    // Nobody may be connected to a template while databases are created
+    // from it.
+    client
+        .execute(
+            "SELECT pg_terminate_backend(pid) FROM pg_stat_activity WHERE datname = $1",
+            &[&building],
+        )
+        .await?;
+    // The build time, for `drop_stale_templates`.
+    let built = Local::now().to_rfc3339();
+    for sql in [
+        format!("COMMENT ON DATABASE {building} IS '{built}'"),
+        format!("ALTER DATABASE {building} ALLOW_CONNECTIONS false"),
+        format!("ALTER DATABASE {building} RENAME TO {template}"),
+    ] {
+        client.batch_execute(&sql).await?;
+    }
+    Ok(())
+}
+
+/// Drops the templates built before `cutoff`, or by versions of pgmt that did
+/// not record when, the tests build them again when they need them.
+pub(crate) async fn drop_stale_templates(
+    client: &deadpool_postgres::Client,
+    cutoff: DateTime<Local>,
+) -> Result<Vec<String>> {
+    with_template_lock(client, async || {
+        let rows = client
+            .query(
+                "SELECT datname, shobj_description(oid, 'pg_database') FROM pg_database \
+                 WHERE starts_with(datname, $1) ORDER BY datname",
+                &[&TEMPLATE_PREFIX],
+            )
+            .await?;
+        let mut dropped = vec![];
+        for row in rows {
+            let name: String = row.get(0);
+            let built: Option<String> = row.get(1);
+            let built = built.and_then(|built| DateTime::parse_from_rfc3339(&built).ok());
+            if built.is_none_or(|built| built < cutoff) {
+                client
+                    .batch_execute(&format!("DROP DATABASE IF EXISTS {name} WITH (FORCE)"))
+                    .await?;
+                dropped.push(name);
+            }
+        }
+        Ok(dropped)
+    })
+    .await
+}
+
+#[test]
+fn templates_change_with_the_migrations() {
+    let file = |name: &str, content: &str| SqlFile {
+        content: content.to_string(),
+        file_name: name.to_string(),
+        file_path: format!("migrations/{name}"),
+    };
+    let files = vec![
+        file("V1__a.sql", "CREATE TABLE a ();"),
+        file("V2__b.sql", "SELECT 1;"),
+    ];
+    let reversed: Vec<SqlFile> = files.iter().rev().cloned().collect();
+    let placeholders = Placeholders::from([("schema".to_string(), "app".to_string())]);
+
+    let fixtures = vec![Fixture {
+        path: "fixtures/users.sql".to_string(),
+        content: "INSERT INTO a DEFAULT VALUES;".to_string(),
+    }];
+
+    let name = template_name(&files, &placeholders, &[]);
+    assert!(name.starts_with("pgmt_template_"));
+    assert_eq!(template_name(&reversed, &placeholders, &[]), name);
+    assert_ne!(template_name(&files, &Placeholders::new(), &[]), name);
+    assert_ne!(template_name(&files[..1], &placeholders, &[]), name);
+    assert_ne!(template_name(&files, &placeholders, &fixtures), name);
+}
@@ -0,0 +1,429 @@
+use crate::error::{ConfigError, Result};
+use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod, Runtime};
+use std::path::PathBuf;
+use std::str::FromStr;
+use tokio_postgres::config::Host;
+use tokio_postgres::tls::{MakeTlsConnect, TlsConnect};
+use tokio_postgres::{NoTls, Socket};
+
+#[cfg(all(feature = "rustls", feature = "native-tls"))]
+compile_error!(
+    "The rustls and native-tls features can not be enabled together, build with `--no-default-features --features native-tls` to use native-tls"
+);
+
+/// How hard to insist on an encrypted connection, the `sslmode` of libpq.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SslMode {
+    /// Never use TLS.
+    Disable,
+    /// Use TLS when the server supports it, without verifying the server.
+    #[default]
+    Prefer,
+    /// Always use TLS, the server is only verified when a root certificate is
+    /// available, like `VerifyCa`.
+    Require,
+    /// Always use TLS and verify that the server certificate is signed by a
+    /// trusted root certificate.
+    VerifyCa,
+    /// Like `VerifyCa` and also verify that the certificate matches the host.
+    VerifyFull,
+}
+
+impl FromStr for SslMode {
+    type Err = ConfigError;
+
+    fn from_str(mode: &str) -> std::result::Result<Self, Self::Err> {
+        match mode {
+            "disable" => Ok(SslMode::Disable),
+            "prefer" => Ok(SslMode::Prefer),
+            "require" => Ok(SslMode::Require),
+            "verify-ca" => Ok(SslMode::VerifyCa),
+            "verify-full" => Ok(SslMode::VerifyFull),
+            _ => Err(ConfigError::new(format!(
+                "Invalid sslmode {mode}, expected disable, prefer, require, verify-ca or verify-full"
+            ))),
+        }
+    }
+}
+
+/// TLS settings of a connection, read from the `sslmode`, `sslrootcert`,
+/// `sslcert` and `sslkey` settings of the connection string, the service or
+/// the `PGSSL*` environment variables.
+///
+/// Like libpq the files default to `root.crt`, `postgresql.crt` and
+/// `postgresql.key` in `~/.postgresql` when they exist, `sslrootcert` can
+/// also be `system` to trust the root certificates of the system.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsConfig {
+    pub mode: SslMode,
+    pub root_cert: Option<String>,
+    pub cert: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+}
+
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+impl TlsConfig {
+    fn verify(&self) -> bool {
+        self.mode == SslMode::VerifyCa || self.mode == SslMode::VerifyFull
+    }
+
+    fn root_cert(&self) -> Result<Option<String>> {
+        match &self.root_cert {
+            Some(root_cert) => Ok(Some(root_cert.clone())),
+            None => match default_file("root.crt") {
+                Some(path) => Ok(Some(path.to_string_lossy().into_owned())),
+                None if self.verify() => Err(ConfigError::new(format!(
+                    "sslmode {:?} needs a root certificate, set sslrootcert or create ~/.postgresql/root.crt",
+                    self.mode
+                ))
+                .into()),
+                None => Ok(None),
+            },
+        }
+    }
+
+    fn client_cert(&self) -> Result<Option<(PathBuf, PathBuf)>> {
+        let cert = self.cert.clone().or_else(|| default_file("postgresql.crt"));
+        let key = self.key.clone().or_else(|| default_file("postgresql.key"));
+        match (cert, key) {
+            (Some(cert), Some(key)) => Ok(Some((cert, key))),
+            (Some(cert), None) => Err(ConfigError::new(format!(
+                "The client certificate {} has no sslkey",
+                cert.display()
+            ))
+            .into()),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+fn default_file(name: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(std::env::var_os("HOME")?)
+        .join(".postgresql")
+        .join(name);
+    path.exists().then_some(path)
+}
+
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+fn read_file(path: &str, what: &str) -> Result<Vec<u8>> {
+    std::fs::read(path).map_err(|e| {
+        ConfigError {
+            message: format!("Unable to read the {what} {path}"),
+            source: Some(Box::new(e)),
+        }
+        .into()
+    })
+}
+
+/// Creates a pool that connects with the TLS settings, like libpq Unix
+/// sockets are never encrypted.
+pub(crate) fn create_pool(mut config: tokio_postgres::Config, tls: &TlsConfig) -> Result<Pool> {
+    let unix = config
+        .get_hosts()
+        .iter()
+        .all(|host| !matches!(host, Host::Tcp(_)));
+    let mode = if unix { SslMode::Disable } else { tls.mode };
+    config.ssl_mode(match mode {
+        SslMode::Disable => tokio_postgres::config::SslMode::Disable,
+        SslMode::Prefer => tokio_postgres::config::SslMode::Prefer,
+        _ => tokio_postgres::config::SslMode::Require,
+    });
+    if mode == SslMode::Disable {
+        return build_pool(config, NoTls);
+    }
+    #[cfg(feature = "rustls")]
+    {
+        build_pool(config, with_rustls::connector(tls)?)
+    }
+    #[cfg(feature = "native-tls")]
+    {
+        build_pool(config, with_native_tls::connector(tls)?)
+    }
+    #[cfg(not(any(feature = "rustls", feature = "native-tls")))]
+    {
+        if mode != SslMode::Prefer {
+            return Err(ConfigError::new(format!(
+                "sslmode {mode:?} needs TLS, pgmt was built without the rustls or native-tls feature"
+            ))
+            .into());
+        }
+        build_pool(config, NoTls)
+    }
+}
+
+fn build_pool<T>(config: tokio_postgres::Config, tls: T) -> Result<Pool>
+where
+    T: MakeTlsConnect<Socket> + Clone + Sync + Send + 'static,
+    T::Stream: Sync + Send,
+    T::TlsConnect: Sync + Send,
+    <T::TlsConnect as TlsConnect<Socket>>::Future: Send,
+{
+    let manager = Manager::from_config(
+        config,
+        tls,
+        ManagerConfig {
+            recycling_method: RecyclingMethod::Fast,
+        },
+    );
+    Ok(Pool::builder(manager).runtime(Runtime::Tokio1).build()?)
+}
+
+#[cfg(feature = "rustls")]
+mod with_rustls {
+    use super::{SslMode, TlsConfig, read_file};
+    use crate::error::{ConfigError, Error, Result};
+    use rustls::client::WebPkiServerVerifier;
+    use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+    use rustls::crypto::{CryptoProvider, verify_tls12_signature, verify_tls13_signature};
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+    use rustls::{
+        CertificateError, ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+    };
+    use std::sync::Arc;
+    use tokio_postgres_rustls::MakeRustlsConnect;
+
+    fn config_error(
+        message: String,
+        source: impl std::error::Error + Send + Sync + 'static,
+    ) -> Error {
+        ConfigError {
+            message,
+            source: Some(Box::new(source)),
+        }
+        .into()
+    }
+
+    pub(super) fn connector(tls: &TlsConfig) -> Result<MakeRustlsConnect> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| config_error("Unable to configure TLS".to_string(), e))?;
+
+        let verifier: Arc<dyn ServerCertVerifier> = match tls.root_cert()? {
+            Some(root_cert) if tls.mode != SslMode::Prefer => {
+                let roots = Arc::new(root_store(&root_cert)?);
+                let webpki = WebPkiServerVerifier::builder_with_provider(roots, provider.clone())
+                    .build()
+                    .map_err(|e| config_error("Unable to configure TLS".to_string(), e))?;
+                if tls.mode == SslMode::VerifyFull {
+                    webpki
+                } else {
+                    Arc::new(SkipHostnameVerification(webpki))
+                }
+            }
+            _ => Arc::new(NoVerification(provider.clone())),
+        };
+        let builder = builder
+            .dangerous()
+            .with_custom_certificate_verifier(verifier);
+
+        let config = match tls.client_cert()? {
+            Some((cert, key)) => {
+                let certs = CertificateDer::pem_file_iter(&cert)
+                    .and_then(|certs| certs.collect::<std::result::Result<Vec<_>, _>>())
+                    .map_err(|e| {
+                        config_error(format!("Unable to read the sslcert {}", cert.display()), e)
+                    })?;
+                let key = PrivateKeyDer::from_pem_file(&key).map_err(|e| {
+                    config_error(format!("Unable to read the sslkey {}", key.display()), e)
+                })?;
+                builder
+                    .with_client_auth_cert(certs, key)
+                    .map_err(|e| config_error("Invalid client certificate".to_string(), e))?
+            }
+            None => builder.with_no_client_auth(),
+        };
+        Ok(MakeRustlsConnect::new(config))
+    }
+
+    fn root_store(root_cert: &str) -> Result<RootCertStore> {
+        let mut roots = RootCertStore::empty();
+        if root_cert == "system" {
+            let native = rustls_native_certs::load_native_certs();
+            if let Some(e) = native.errors.into_iter().next()
+                && native.certs.is_empty()
+            {
+                return Err(config_error(
+                    "Unable to load the system root certificates".to_string(),
+                    e,
+                ));
+            }
+            roots.add_parsable_certificates(native.certs);
+            return Ok(roots);
+        }
+        let pem = read_file(root_cert, "sslrootcert")?;
+        for cert in CertificateDer::pem_slice_iter(&pem) {
+            let cert = cert.map_err(|e| {
+                config_error(
+                    format!("Invalid certificate in the sslrootcert {root_cert}"),
+                    e,
+                )
+            })?;
+            roots.add(cert).map_err(|e| {
+                config_error(
+                    format!("Invalid certificate in the sslrootcert {root_cert}"),
+                    e,
+                )
+            })?;
+        }
+        Ok(roots)
+    }
+
+    /// Accepts any certificate, used by `prefer` and by `require` without a
+    /// root certificate, which only ask for encryption.
+    #[derive(Debug)]
+    struct NoVerification(Arc<CryptoProvider>);
+
+    impl ServerCertVerifier for NoVerification {
+        fn verify_server_cert(
+            &self,
+            _end_entity: &CertificateDer<'_>,
+            _intermediates: &[CertificateDer<'_>],
+            _server_name: &ServerName<'_>,
+            _ocsp_response: &[u8],
+            _now: UnixTime,
+        ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+            Ok(ServerCertVerified::assertion())
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+            verify_tls12_signature(
+                message,
+                cert,
+                dss,
+                &self.0.signature_verification_algorithms,
+            )
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+            verify_tls13_signature(
+                message,
+                cert,
+                dss,
+                &self.0.signature_verification_algorithms,
+            )
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            self.0.signature_verification_algorithms.supported_schemes()
+        }
+    }
+
+    /// Verifies the certificate chain but not the host name, `verify-ca`.
+    #[derive(Debug)]
+    struct SkipHostnameVerification(Arc<WebPkiServerVerifier>);
+
+    impl ServerCertVerifier for SkipHostnameVerification {
+        fn verify_server_cert(
+            &self,
+            end_entity: &CertificateDer<'_>,
+            intermediates: &[CertificateDer<'_>],
+            server_name: &ServerName<'_>,
+            ocsp_response: &[u8],
+            now: UnixTime,
+        ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+            match self.0.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                ocsp_response,
+                now,
+            ) {
+                Err(rustls::Error::InvalidCertificate(
+                    CertificateError::NotValidForName
+                    | CertificateError::NotValidForNameContext { .. },
+                )) => Ok(ServerCertVerified::assertion()),
+                result => result,
+            }
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+            self.0.verify_tls12_signature(message, cert, dss)
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+            self.0.verify_tls13_signature(message, cert, dss)
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            self.0.supported_verify_schemes()
+        }
+    }
+}
+
+#[cfg(feature = "native-tls")]
+mod with_native_tls {
+    use super::{SslMode, TlsConfig, read_file};
+    use crate::error::{ConfigError, Error, Result};
+    use native_tls::{Certificate, Identity, TlsConnector};
+    use postgres_native_tls::MakeTlsConnector;
+
+    fn config_error(message: String, source: native_tls::Error) -> Error {
+        ConfigError {
+            message,
+            source: Some(Box::new(source)),
+        }
+        .into()
+    }
+
+    pub(super) fn connector(tls: &TlsConfig) -> Result<MakeTlsConnector> {
+        let mut builder = TlsConnector::builder();
+        match tls.root_cert()? {
+            Some(root_cert) if tls.mode != SslMode::Prefer => {
+                if root_cert != "system" {
+                    let pem = read_file(&root_cert, "sslrootcert")?;
+                    let certs = Certificate::stack_from_pem(&pem).map_err(|e| {
+                        config_error(
+                            format!("Invalid certificate in the sslrootcert {root_cert}"),
+                            e,
+                        )
+                    })?;
+                    builder.disable_built_in_roots(true);
+                    for cert in certs {
+                        builder.add_root_certificate(cert);
+                    }
+                }
+                if tls.mode != SslMode::VerifyFull {
+                    builder.danger_accept_invalid_hostnames(true);
+                }
+            }
+            _ => {
+                builder.danger_accept_invalid_certs(true);
+            }
+        }
+        if let Some((cert, key)) = tls.client_cert()? {
+            let cert_pem = read_file(&cert.to_string_lossy(), "sslcert")?;
+            let key_pem = read_file(&key.to_string_lossy(), "sslkey")?;
+            let identity = Identity::from_pkcs8(&cert_pem, &key_pem).map_err(|e| {
+                config_error(format!("Invalid client certificate {}", cert.display()), e)
+            })?;
+            builder.identity(identity);
+        }
+        let connector = builder
+            .build()
+            .map_err(|e| config_error("Unable to configure TLS".to_string(), e))?;
+        Ok(MakeTlsConnector::new(connector))
+    }
+}
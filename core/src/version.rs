@@ -0,0 +1,175 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+/// A Flyway compatible migration version like `1`, `1.2`, `2_1` or `20240105`.
+///
+/// The version is made of numeric parts separated by `.` or `_`, of any length
+/// and with any number of digits. Parts are compared numerically and missing
+/// trailing parts count as zero, so `1.10` is newer than `1.9` and `1` is the
+/// same version as `1.0`.
+///
+/// The version is displayed with the parts joined by `.`, the same way Flyway
+/// writes it to the history table, and parsing the displayed version gives
+/// back an equal version.
+#[derive(Debug, Clone)]
+pub struct MigrationVersion {
+    parts: Vec<String>,
+}
+
+impl MigrationVersion {
+    /// The numeric parts of the version as they were written.
+    pub fn parts(&self) -> &[String] {
+        &self.parts
+    }
+
+    /// The parts with leading zeros and trailing zero parts removed, used for
+    /// comparing and hashing.
+    fn normalized(&self) -> Vec<&str> {
+        let mut parts: Vec<&str> = self
+            .parts
+            .iter()
+            .map(|part| match part.trim_start_matches('0') {
+                "" => "0",
+                trimmed => trimmed,
+            })
+            .collect();
+        while parts.len() > 1 && parts.last() == Some(&"0") {
+            parts.pop();
+        }
+        parts
+    }
+}
+
+impl FromStr for MigrationVersion {
+    type Err = String;
+
+    fn from_str(version: &str) -> Result<Self, Self::Err> {
+        if version.is_empty() {
+            return Err("version is empty".to_string());
+        }
+        let parts: Vec<String> = version.split(['.', '_']).map(str::to_string).collect();
+        if let Some(part) = parts
+            .iter()
+            .find(|part| part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()))
+        {
+            return Err(if part.is_empty() {
+                format!("version `{version}` has an empty part")
+            } else {
+                format!("version `{version}` has a non numeric part `{part}`")
+            });
+        }
+        Ok(Self { parts })
+    }
+}
+
+impl fmt::Display for MigrationVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.parts.join("."))
+    }
+}
+
+impl Ord for MigrationVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let a = self.normalized();
+        let b = other.normalized();
+        for i in 0..a.len().max(b.len()) {
+            let a = a.get(i).copied().unwrap_or("0");
+            let b = b.get(i).copied().unwrap_or("0");
+            // Both parts are digits without leading zeros, so the longer one
+            // is the larger number.
+            match a.len().cmp(&b.len()).then_with(|| a.cmp(b)) {
+                Ordering::Equal => continue,
+                ordering => return ordering,
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+impl PartialOrd for MigrationVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for MigrationVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for MigrationVersion {}
+
+impl Hash for MigrationVersion {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.normalized().hash(state);
+    }
+}
+
+#[test]
+fn test_parse() {
+    use pretty_assertions::assert_eq;
+    let parse = |v: &str| v.parse::<MigrationVersion>().map(|v| v.to_string());
+    assert_eq!(parse("1"), Ok("1".to_string()));
+    assert_eq!(parse("1.2"), Ok("1.2".to_string()));
+    assert_eq!(parse("2_1"), Ok("2.1".to_string()));
+    assert_eq!(parse("1.0.0"), Ok("1.0.0".to_string()));
+    assert_eq!(parse("20240105"), Ok("20240105".to_string()));
+    assert_eq!(
+        parse("20240105123000_1"),
+        Ok("20240105123000.1".to_string())
+    );
+    assert_eq!(parse(""), Err("version is empty".to_string()));
+    assert_eq!(
+        parse("1..2"),
+        Err("version `1..2` has an empty part".to_string())
+    );
+    assert_eq!(
+        parse("1.0.0-alpha"),
+        Err("version `1.0.0-alpha` has a non numeric part `0-alpha`".to_string())
+    );
+}
+
+#[test]
+fn test_round_trip() {
+    for version in ["1", "1.2", "2_1", "1.0.0", "20240105", "007"] {
+        let parsed: MigrationVersion = version.parse().unwrap();
+        let reparsed: MigrationVersion = parsed.to_string().parse().unwrap();
+        assert_eq!(parsed, reparsed);
+        assert_eq!(parsed.to_string(), reparsed.to_string());
+    }
+}
+
+#[test]
+fn test_ordering() {
+    use pretty_assertions::assert_eq;
+    let v = |v: &str| v.parse::<MigrationVersion>().unwrap();
+    assert!(v("1") < v("2"));
+    assert!(v("1.9") < v("1.10"));
+    assert!(v("2") < v("10"));
+    assert!(v("1.2") < v("1.2.1"));
+    assert!(v("2_1") > v("2"));
+    assert!(v("1.99999999999999999999999") < v("2"));
+    assert!(v("20240105") > v("1.0.0"));
+    assert_eq!(v("1"), v("1.0"));
+    assert_eq!(v("1.0"), v("1.0.0"));
+    assert_eq!(v("01.2"), v("1.2"));
+    assert_eq!(v("2_1"), v("2.1"));
+
+    let mut versions = [v("1.10"), v("20240105"), v("1.2"), v("1"), v("1.9")];
+    versions.sort();
+    let versions: Vec<String> = versions.iter().map(|v| v.to_string()).collect();
+    assert_eq!(versions, vec!["1", "1.2", "1.9", "1.10", "20240105"]);
+}
+
+#[test]
+fn test_hash_matches_eq() {
+    use std::collections::HashSet;
+    let v = |v: &str| v.parse::<MigrationVersion>().unwrap();
+    let set: HashSet<MigrationVersion> = [v("1"), v("1.0"), v("01.0.0"), v("2_1"), v("2.1")]
+        .into_iter()
+        .collect();
+    assert_eq!(set.len(), 2);
+}
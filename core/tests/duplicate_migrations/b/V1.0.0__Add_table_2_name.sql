@@ -0,0 +1,5 @@
+CREATE TABLE table_2_name (
+  name     TEXT      NOT NULL,
+  closed   BOOL      NOT NULL,
+  "offset" BIGSERIAL NOT NULL
+);
@@ -0,0 +1 @@
+CREATE TABLE valid (id INT);
@@ -0,0 +1,52 @@
+use pgmt_core::test_db;
+
+async fn database_exists(db_name: &str) -> bool {
+    let pool = pgmt_core::connect(std::env::var("PGMT_TEST_DB_URL").unwrap_or_default())
+        .await
+        .unwrap();
+    let client = pool.get().await.unwrap();
+    client
+        .query_opt("SELECT FROM pg_database WHERE datname = $1", &[&db_name])
+        .await
+        .unwrap()
+        .is_some()
+}
+
+fn db_name(url: &str) -> String {
+    url.rsplit('/').next().unwrap().to_string()
+}
+
+// The only test of this binary, PGMT_KEEP_DB is read by every test database.
+#[tokio::test]
+async fn keep_test_databases() {
+    unsafe { std::env::set_var("PGMT_KEEP_DB", "on-failure") };
+    let passed = test_db(async |_pool, url| db_name(&url)).await;
+    assert!(passed.ends_with("_keep_test_databases"), "{passed}");
+    assert!(!database_exists(&passed).await);
+
+    let failed = std::sync::Arc::new(std::sync::Mutex::new(String::new()));
+    let test_failed = failed.clone();
+    let test = tokio::spawn(async move {
+        test_db(async |_pool, url| {
+            *test_failed.lock().unwrap() = db_name(&url);
+            panic!("assertion failed");
+        })
+        .await
+    });
+    assert!(test.await.unwrap_err().is_panic());
+    let failed = failed.lock().unwrap().clone();
+    assert!(database_exists(&failed).await);
+
+    unsafe { std::env::set_var("PGMT_KEEP_DB", "always") };
+    let kept = test_db(async |_pool, url| db_name(&url)).await;
+    assert!(database_exists(&kept).await);
+
+    for db_name in [failed, kept] {
+        pgmt_core::teardown(
+            std::env::var("PGMT_TEST_DB_URL").unwrap_or_default(),
+            &db_name,
+        )
+        .await
+        .unwrap();
+    }
+}
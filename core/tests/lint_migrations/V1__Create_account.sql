@@ -0,0 +1,5 @@
+CREATE TABLE account (
+  id    BIGINT NOT NULL,
+  name  TEXT   NOT NULL
+);
+CREATE INDEX account_name_index ON account (name);
@@ -0,0 +1,5 @@
+-- pgmt-lint: allow rename
+ALTER TABLE account
+  ADD COLUMN email TEXT NOT NULL;
+ALTER TABLE account RENAME COLUMN name TO full_name;
+CREATE INDEX account_email_index ON account (email);
@@ -1,9 +1,12 @@
 use chrono::{DateTime, Utc};
 use pgmt_core::tests_helper::{
-    get_schema_history_rows, get_table_columns, get_table_names, new_schema_history_columns,
-    SchemaHistoryRow, TableColumn,
+    SchemaHistoryRow, TableColumn, get_schema_history_rows, get_table_columns, get_table_names,
+    new_schema_history_columns,
+};
+use pgmt_core::{
+    DiscoveryOptions, Error, Placeholders, SqlFile, migrate, migrate_files, migration_dirs,
+    test_db, vec_of_string,
 };
-use pgmt_core::{migrate, migrate_files, vec_of_string, Placeholders, SqlFile};
 use pretty_assertions::assert_eq;
 
 #[tokio::test]
@@ -117,6 +120,8 @@ async fn only_migrate_the_latest_file() {
             .map(|mut f| {
                 // Overwrite installed_on sicne we are not testint he time stamp
                 f.installed_on = date_time;
+                // The execution time is measured and will differ between runs
+                f.execution_time = 0;
                 f
             })
             .collect();
@@ -128,7 +133,7 @@ async fn only_migrate_the_latest_file() {
                 description: "migration.sql".to_string(),
                 r#type: "V".to_string(),
                 script: "V1.0.0__migration.sql".to_string(),
-                checksum: -228401567,
+                checksum: -1100618949,
                 installed_by: "installed_by".to_string(),
                 installed_on: date_time,
                 execution_time: 0,
@@ -216,5 +221,118 @@ async fn some_test_find_me_a_better_name() {
     .await;
 }
 
+#[tokio::test]
+async fn migrate_nested_migration_directories() {
+    test_db(async |pool, url| {
+        let options = DiscoveryOptions {
+            exclude: vec_of_string!["drafts"],
+            ..Default::default()
+        };
+        migration_dirs(
+            vec!["tests/nested_migrations"],
+            url,
+            Placeholders::new(),
+            options,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            get_table_names(&pool).await,
+            vec_of_string!["_schema_history", "table_1_name", "table_2_name"]
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn duplicate_versions_in_two_directories_fail() {
+    test_db(async |pool, url| {
+        let res = migration_dirs(
+            vec![
+                "tests/duplicate_migrations/a",
+                "tests/duplicate_migrations/b",
+            ],
+            url,
+            Placeholders::new(),
+            DiscoveryOptions::default(),
+        )
+        .await;
+        let Err(Error::DuplicateVersionError(error)) = res else {
+            panic!("Expected DuplicateVersionError, got {res:?}");
+        };
+        assert_eq!(error.prefix, "V");
+        assert_eq!(error.version, "1.0.0");
+        assert_eq!(
+            error.file_paths,
+            vec_of_string![
+                "tests/duplicate_migrations/a/V1.0.0__Create_table_1_name.sql",
+                "tests/duplicate_migrations/b/V1.0.0__Add_table_2_name.sql",
+            ]
+        );
+        assert_eq!(get_table_names(&pool).await, Vec::<String>::new());
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn flyway_versions_are_migrated_in_numeric_order() {
+    let file = |file_name: &str, content: &str| SqlFile {
+        content: content.into(),
+        file_name: file_name.into(),
+        file_path: format!("migrations/{file_name}"),
+    };
+    let files = vec![
+        file(
+            "V20240105__Add_created.sql",
+            "ALTER TABLE t ADD created INT;",
+        ),
+        file("V1.10__Add_d.sql", "ALTER TABLE t ADD d INT;"),
+        file("V2_1__Add_e.sql", "ALTER TABLE t ADD e INT;"),
+        file("V1.2__Add_c.sql", "ALTER TABLE t ADD c INT;"),
+        file("V1__init.sql", "CREATE TABLE t (id INT);"),
+    ];
+
+    migrate_files(files, None, async |pool| {
+        let versions: Vec<Option<String>> = get_schema_history_rows(&pool)
+            .await
+            .into_iter()
+            .map(|row| row.version)
+            .collect();
+        assert_eq!(
+            versions,
+            vec![
+                Some("1".to_string()),
+                Some("1.2".to_string()),
+                Some("1.10".to_string()),
+                Some("2.1".to_string()),
+                Some("20240105".to_string()),
+            ]
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn equal_flyway_versions_are_duplicates() {
+    let files = vec![
+        SqlFile {
+            content: "CREATE TABLE t1 (id INT);".into(),
+            file_name: "V1__First.sql".into(),
+            file_path: "migrations/V1__First.sql".into(),
+        },
+        SqlFile {
+            content: "CREATE TABLE t2 (id INT);".into(),
+            file_name: "V1.0__Second.sql".into(),
+            file_path: "migrations/V1.0__Second.sql".into(),
+        },
+    ];
+
+    migrate_files(vec![], None, async |pool| {
+        let res = migrate(&pool, files, Placeholders::new()).await;
+        assert!(matches!(res, Err(Error::DuplicateVersionError(_))));
+    })
+    .await;
+}
+
 // TODOs
 // - test both files with CRLF and LF to make shure the content is normalized.
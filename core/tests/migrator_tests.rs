@@ -0,0 +1,1101 @@
+use pgmt_core::tests_helper::{get_schema_history_rows, get_table_names};
+use pgmt_core::{
+    AppliedMigration, BoxError, Error, ErrorCategory, LintOptions, MigrationEvent,
+    MigrationObserver, MigrationState, Migrator, RustMigration, SessionSettings, SkippedMigration,
+    SqlFile, Transaction, TransactionMode, ValidationIssue, async_trait, migrate_files,
+    vec_of_string,
+};
+use pretty_assertions::assert_eq;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+fn file(file_name: &str, content: &str) -> SqlFile {
+    SqlFile {
+        content: content.into(),
+        file_name: file_name.into(),
+        file_path: format!("migrations/{file_name}"),
+    }
+}
+
+fn files() -> Vec<SqlFile> {
+    vec![
+        file("V1__Create_t1.sql", "CREATE TABLE t1 (id INT);"),
+        file("U1__Drop_t1.sql", "DROP TABLE t1;"),
+        file("V2__Create_t2.sql", "CREATE TABLE t2 (id INT);"),
+        file("U2__Drop_t2.sql", "DROP TABLE t2;"),
+        file("V3__Create_t3.sql", "CREATE TABLE t3 (id INT);"),
+    ]
+}
+
+#[tokio::test]
+async fn migrate_reports_the_applied_migrations() {
+    migrate_files(vec![], None, async |pool| {
+        let report = Migrator::new()
+            .sources(files())
+            .migrate(&pool)
+            .await
+            .unwrap();
+        let scripts: Vec<String> = report.applied.into_iter().map(|m| m.script).collect();
+        assert_eq!(
+            scripts,
+            vec_of_string![
+                "V1__Create_t1.sql",
+                "V2__Create_t2.sql",
+                "V3__Create_t3.sql"
+            ]
+        );
+
+        let report = Migrator::new()
+            .sources(files())
+            .migrate(&pool)
+            .await
+            .unwrap();
+        assert!(report.applied.is_empty());
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn migrate_up_to_the_target() {
+    migrate_files(vec![], None, async |pool| {
+        let report = Migrator::new()
+            .sources(files())
+            .target("2")
+            .unwrap()
+            .migrate(&pool)
+            .await
+            .unwrap();
+        assert_eq!(report.applied.len(), 2);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].state, MigrationState::AboveTarget);
+        assert_eq!(
+            get_table_names(&pool).await,
+            vec_of_string!["_schema_history", "t1", "t2"]
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn out_of_order_migrations_fail_unless_allowed() {
+    migrate_files(vec![], None, async |pool| {
+        let mut without_v2 = files();
+        without_v2.retain(|f| f.file_name != "V2__Create_t2.sql");
+        Migrator::new()
+            .sources(without_v2)
+            .migrate(&pool)
+            .await
+            .unwrap();
+
+        let res = Migrator::new().sources(files()).migrate(&pool).await;
+        let Err(Error::OutOfOrderError(error)) = res else {
+            panic!("Expected OutOfOrderError, got {res:?}");
+        };
+        assert_eq!(error.script, "V2__Create_t2.sql");
+        assert_eq!(error.latest_version, "3");
+
+        let report = Migrator::new()
+            .sources(files())
+            .out_of_order(true)
+            .migrate(&pool)
+            .await
+            .unwrap();
+        assert_eq!(report.applied.len(), 1);
+        assert_eq!(report.applied[0].script, "V2__Create_t2.sql");
+        assert_eq!(
+            report.warnings,
+            vec_of_string![
+                "Migration V2__Create_t2.sql is applied out of order, the latest applied version is 3"
+            ]
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn undo_the_latest_migration_and_down_to_a_target() {
+    migrate_files(vec![], None, async |pool| {
+        let mut files = files();
+        files.retain(|f| f.file_name != "V3__Create_t3.sql");
+        Migrator::new()
+            .sources(files.clone())
+            .migrate(&pool)
+            .await
+            .unwrap();
+
+        let report = Migrator::new()
+            .sources(files.clone())
+            .undo(&pool)
+            .await
+            .unwrap();
+        assert_eq!(report.undone[0].script, "U2__Drop_t2.sql");
+        assert_eq!(
+            get_table_names(&pool).await,
+            vec_of_string!["_schema_history", "t1"]
+        );
+
+        // The undone migration is pending again.
+        let report = Migrator::new()
+            .sources(files.clone())
+            .migrate(&pool)
+            .await
+            .unwrap();
+        assert_eq!(report.applied[0].script, "V2__Create_t2.sql");
+
+        let report = Migrator::new()
+            .sources(files)
+            .target("0")
+            .unwrap()
+            .undo(&pool)
+            .await
+            .unwrap();
+        let scripts: Vec<String> = report.undone.into_iter().map(|m| m.script).collect();
+        assert_eq!(
+            scripts,
+            vec_of_string!["U2__Drop_t2.sql", "U1__Drop_t1.sql"]
+        );
+        assert_eq!(
+            get_table_names(&pool).await,
+            vec_of_string!["_schema_history"]
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn info_and_validate() {
+    migrate_files(vec![], None, async |pool| {
+        let mut first = files();
+        first.retain(|f| f.file_name == "V1__Create_t1.sql");
+        Migrator::new().sources(first).migrate(&pool).await.unwrap();
+
+        let report = Migrator::new().sources(files()).info(&pool).await.unwrap();
+        let states: Vec<(Option<String>, MigrationState)> = report
+            .migrations
+            .into_iter()
+            .map(|m| (m.version, m.state))
+            .collect();
+        assert_eq!(
+            states,
+            vec![
+                (Some("1".to_string()), MigrationState::Applied),
+                (Some("2".to_string()), MigrationState::Pending),
+                (Some("3".to_string()), MigrationState::Pending),
+            ]
+        );
+
+        let report = Migrator::new()
+            .sources(files())
+            .validate(&pool)
+            .await
+            .unwrap();
+        assert!(report.is_valid());
+        assert_eq!(
+            report.pending,
+            vec_of_string!["V2__Create_t2.sql", "V3__Create_t3.sql"]
+        );
+
+        let report = Migrator::new()
+            .sources(vec![file("V2__Create_t2.sql", "CREATE TABLE t2 (id INT);")])
+            .validate(&pool)
+            .await
+            .unwrap();
+        assert!(matches!(
+            report.issues.as_slice(),
+            [ValidationIssue::Missing(missing)] if missing.script == "V1__Create_t1.sql"
+        ));
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn edited_migrations_are_found_and_realigned() {
+    migrate_files(vec![], None, async |pool| {
+        Migrator::new().sources(files()).migrate(&pool).await.unwrap();
+        let mut edited = files();
+        edited[0].content = "CREATE TABLE t1 (id BIGINT);".into();
+
+        let report = Migrator::new()
+            .sources(edited.clone())
+            .validate(&pool)
+            .await
+            .unwrap();
+        assert!(matches!(
+            report.issues.as_slice(),
+            [ValidationIssue::ChecksumMismatch(mismatch)] if mismatch.file_name == "V1__Create_t1.sql"
+        ));
+
+        let report = Migrator::new()
+            .sources(edited.clone())
+            .repair(&pool)
+            .await
+            .unwrap();
+        assert_eq!(report.realigned, vec_of_string!["V1__Create_t1.sql"]);
+        let report = Migrator::new()
+            .sources(edited)
+            .validate(&pool)
+            .await
+            .unwrap();
+        assert!(report.is_valid());
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn legacy_checksums_are_rewritten_on_the_next_migrate() {
+    migrate_files(vec![], None, async |pool| {
+        Migrator::new().sources(files()).migrate(&pool).await.unwrap();
+        // Earlier versions of pgmt recorded crc32("foo bar baz") for every
+        // migration.
+        pool.get()
+            .await
+            .unwrap()
+            .batch_execute("UPDATE _schema_history SET checksum = -228401567")
+            .await
+            .unwrap();
+
+        let report = Migrator::new().sources(files()).validate(&pool).await.unwrap();
+        assert!(report.is_valid(), "{:?}", report.issues);
+        Migrator::new().sources(files()).migrate(&pool).await.unwrap();
+        let checksums: Vec<i32> = get_schema_history_rows(&pool)
+            .await
+            .into_iter()
+            .map(|row| row.checksum)
+            .collect();
+        assert!(!checksums.contains(&-228401567), "{checksums:?}");
+
+        let mut edited = files();
+        edited[0].content = "CREATE TABLE t1 (id BIGINT);".into();
+        let report = Migrator::new().sources(edited).validate(&pool).await.unwrap();
+        assert!(matches!(
+            report.issues.as_slice(),
+            [ValidationIssue::ChecksumMismatch(mismatch)] if mismatch.file_name == "V1__Create_t1.sql"
+        ));
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn baseline_skips_the_migrations_at_or_below_the_baseline() {
+    migrate_files(vec![], None, async |pool| {
+        Migrator::new()
+            .baseline_version("2")
+            .unwrap()
+            .baseline(&pool)
+            .await
+            .unwrap();
+        let report = Migrator::new()
+            .sources(files())
+            .migrate(&pool)
+            .await
+            .unwrap();
+        let scripts: Vec<String> = report.applied.into_iter().map(|m| m.script).collect();
+        assert_eq!(scripts, vec_of_string!["V3__Create_t3.sql"]);
+
+        let res = Migrator::new().baseline(&pool).await;
+        assert!(res.is_err());
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn repair_removes_failed_migrations() {
+    migrate_files(vec![], None, async |pool| {
+        let broken = vec![file(
+            "V1__Broken.sql",
+            "CREATE TABLE t1 (id INT); SELECT nope;",
+        )];
+        let res = Migrator::new()
+            .sources(broken)
+            .transaction_mode(TransactionMode::None)
+            .migrate(&pool)
+            .await;
+        assert!(res.is_err());
+
+        let fixed = vec![file(
+            "V1__Broken.sql",
+            "CREATE TABLE IF NOT EXISTS t1 (id INT);",
+        )];
+        let res = Migrator::new().sources(fixed.clone()).migrate(&pool).await;
+        assert!(matches!(res, Err(Error::FailedMigrationError(_))));
+
+        let report = Migrator::new()
+            .sources(fixed.clone())
+            .repair(&pool)
+            .await
+            .unwrap();
+        assert_eq!(report.removed_failed, vec_of_string!["V1__Broken.sql"]);
+
+        let report = Migrator::new().sources(fixed).migrate(&pool).await.unwrap();
+        assert_eq!(report.applied.len(), 1);
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn all_migrations_in_one_transaction() {
+    migrate_files(vec![], None, async |pool| {
+        let mut files = files();
+        files.push(file("V4__Broken.sql", "SELECT nope;"));
+        let res = Migrator::new()
+            .sources(files)
+            .transaction_mode(TransactionMode::All)
+            .migrate(&pool)
+            .await;
+        assert!(res.is_err());
+        assert_eq!(
+            get_table_names(&pool).await,
+            vec_of_string!["_schema_history"]
+        );
+        assert_eq!(get_schema_history_rows(&pool).await, vec![]);
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn custom_history_table_and_hooks() {
+    migrate_files(vec![], None, async |pool| {
+        let mut files = files();
+        files.push(file(
+            "beforeMigrate.sql",
+            "CREATE SCHEMA IF NOT EXISTS ${schema};",
+        ));
+        files.push(file(
+            "afterEachMigrate.sql",
+            "CREATE TABLE IF NOT EXISTS hook_runs (id SERIAL); INSERT INTO hook_runs DEFAULT VALUES;",
+        ));
+        Migrator::new()
+            .sources(files)
+            .placeholder("schema", "history")
+            .history_table("history.migrations")
+            .migrate(&pool)
+            .await
+            .unwrap();
+
+        let client = pool.get().await.unwrap();
+        let applied: i64 = client
+            .query_one("SELECT count(*) FROM history.migrations", &[])
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(applied, 3);
+        let hook_runs: i64 = client
+            .query_one("SELECT count(*) FROM hook_runs", &[])
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(hook_runs, 3);
+    })
+    .await;
+}
+
+#[derive(Default)]
+struct RecordingObserver {
+    events: Mutex<Vec<String>>,
+}
+
+impl MigrationObserver for RecordingObserver {
+    fn started(&self, migration: &MigrationEvent) {
+        let event = format!("started {}", migration.script);
+        self.events.lock().unwrap().push(event);
+    }
+
+    fn applied(&self, migration: &AppliedMigration) {
+        let event = format!("applied {}", migration.script);
+        self.events.lock().unwrap().push(event);
+    }
+
+    fn failed(&self, migration: &MigrationEvent, _error: &Error) {
+        let event = format!("failed {}", migration.script);
+        self.events.lock().unwrap().push(event);
+    }
+
+    fn skipped(&self, migration: &SkippedMigration) {
+        let event = format!("skipped {}", migration.script);
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+#[tokio::test]
+async fn observers_are_notified() {
+    migrate_files(vec![], None, async |pool| {
+        let observer = Arc::new(RecordingObserver::default());
+        let mut with_broken = files();
+        with_broken.push(file("V4__Broken.sql", "SELECT nope;"));
+        let res = Migrator::new()
+            .sources(with_broken.clone())
+            .target("3")
+            .unwrap()
+            .observer(observer.clone())
+            .migrate(&pool)
+            .await;
+        assert!(res.is_ok());
+
+        let res = Migrator::new()
+            .sources(with_broken)
+            .observer(observer.clone())
+            .migrate(&pool)
+            .await;
+        assert!(res.is_err());
+
+        assert_eq!(
+            *observer.events.lock().unwrap(),
+            vec_of_string![
+                "skipped V4__Broken.sql",
+                "started V1__Create_t1.sql",
+                "applied V1__Create_t1.sql",
+                "started V2__Create_t2.sql",
+                "applied V2__Create_t2.sql",
+                "started V3__Create_t3.sql",
+                "applied V3__Create_t3.sql",
+                "started V4__Broken.sql",
+                "failed V4__Broken.sql",
+            ]
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn observers_only_hear_about_committed_migrations() {
+    migrate_files(vec![], None, async |pool| {
+        let observer = Arc::new(RecordingObserver::default());
+        let mut with_broken = files();
+        with_broken.push(file("V4__Broken.sql", "SELECT nope;"));
+        let res = Migrator::new()
+            .sources(with_broken)
+            .transaction_mode(TransactionMode::All)
+            .observer(observer.clone())
+            .migrate(&pool)
+            .await;
+        assert!(res.is_err());
+
+        // Rolled back with the broken migration, none of them was applied.
+        assert_eq!(
+            *observer.events.lock().unwrap(),
+            vec_of_string![
+                "started V1__Create_t1.sql",
+                "started V2__Create_t2.sql",
+                "started V3__Create_t3.sql",
+                "started V4__Broken.sql",
+                "failed V4__Broken.sql",
+            ]
+        );
+
+        observer.events.lock().unwrap().clear();
+        Migrator::new()
+            .sources(files())
+            .transaction_mode(TransactionMode::All)
+            .observer(observer.clone())
+            .migrate(&pool)
+            .await
+            .unwrap();
+        assert_eq!(
+            *observer.events.lock().unwrap(),
+            vec_of_string![
+                "started V1__Create_t1.sql",
+                "started V2__Create_t2.sql",
+                "started V3__Create_t3.sql",
+                "applied V1__Create_t1.sql",
+                "applied V2__Create_t2.sql",
+                "applied V3__Create_t3.sql",
+            ]
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn execution_errors_carry_the_database_error() {
+    migrate_files(vec![], None, async |pool| {
+        let broken = vec![file(
+            "V1__Broken.sql",
+            "CREATE TABLE t1 (id INT);\nINSERT INTO t1 VALUES ('x');",
+        )];
+        let res = Migrator::new().sources(broken).migrate(&pool).await;
+        let Err(error) = res else {
+            panic!("Expected an error, got {res:?}");
+        };
+        assert_eq!(error.category(), ErrorCategory::Execution);
+        assert_eq!(error.sql_state(), Some("22P02"));
+        assert!(std::error::Error::source(&error).is_some());
+        let Error::ExecutionError(error) = error else {
+            panic!("Expected ExecutionError, got {error:?}");
+        };
+        assert_eq!(error.script, "V1__Broken.sql");
+        assert_eq!(error.version.as_deref(), Some("1"));
+        assert_eq!(error.position, Some(50));
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn corrupt_history_rows_are_reported() {
+    migrate_files(vec![], None, async |pool| {
+        Migrator::new()
+            .sources(files())
+            .migrate(&pool)
+            .await
+            .unwrap();
+        let client = pool.get().await.unwrap();
+        client
+            .execute(
+                "UPDATE _schema_history SET version = 'two' WHERE version = '2'",
+                &[],
+            )
+            .await
+            .unwrap();
+
+        let res = Migrator::new().sources(files()).migrate(&pool).await;
+        let Err(Error::HistoryCorruptionError(error)) = res else {
+            panic!("Expected HistoryCorruptionError, got {res:?}");
+        };
+        assert_eq!(error.table, "_schema_history");
+        assert_eq!(error.reason, "version `two` has a non numeric part `two`");
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn errors_are_categorized() {
+    migrate_files(vec![], None, async |pool| {
+        let res = Migrator::new()
+            .sources(vec![file("V1__Placeholder.sql", "SELECT ${missing};")])
+            .migrate(&pool)
+            .await;
+        assert_eq!(res.unwrap_err().category(), ErrorCategory::Template);
+
+        let res = Migrator::new().target("1.x");
+        assert_eq!(res.unwrap_err().category(), ErrorCategory::Config);
+
+        let res = Migrator::new()
+            .location("tests/does_not_exist")
+            .migrate(&pool)
+            .await;
+        assert_eq!(res.unwrap_err().category(), ErrorCategory::Discovery);
+
+        // V3 has no undo migration.
+        Migrator::new()
+            .sources(files())
+            .migrate(&pool)
+            .await
+            .unwrap();
+        let res = Migrator::new().sources(files()).undo(&pool).await;
+        assert!(matches!(res, Err(Error::MissingUndoError(_))));
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn migrations_run_with_the_session_settings() {
+    migrate_files(vec![], None, async |pool| {
+        let settings = SessionSettings {
+            lock_timeout: Some("2s".to_string()),
+            statement_timeout: Some("1min".to_string()),
+            ..SessionSettings::default()
+        };
+        let migrations = vec![
+            file(
+                "V1__Settings.sql",
+                "-- pgmt:lock_timeout = 5s\n-- pgmt:search_path = pg_catalog, public\nCREATE TABLE public.s1 AS SELECT current_setting('lock_timeout') AS lock_timeout, current_setting('statement_timeout') AS statement_timeout, current_setting('search_path') AS search_path;",
+            ),
+            file(
+                "V2__Defaults.sql",
+                "CREATE TABLE s2 AS SELECT current_setting('lock_timeout') AS lock_timeout, current_setting('search_path') AS search_path;",
+            ),
+        ];
+        for mode in [TransactionMode::All, TransactionMode::None] {
+            let client = pool.get().await.unwrap();
+            client
+                .batch_execute("DROP TABLE IF EXISTS s1, s2, _schema_history")
+                .await
+                .unwrap();
+            Migrator::new()
+                .sources(migrations.clone())
+                .session_settings(settings.clone())
+                .transaction_mode(mode)
+                .migrate(&pool)
+                .await
+                .unwrap();
+            let row = client.query_one("SELECT * FROM s1", &[]).await.unwrap();
+            let values: (String, String, String) = (row.get(0), row.get(1), row.get(2));
+            assert_eq!(
+                values,
+                ("5s".into(), "1min".into(), "pg_catalog, public".into())
+            );
+            let row = client.query_one("SELECT * FROM s2", &[]).await.unwrap();
+            let values: (String, String) = (row.get(0), row.get(1));
+            assert_eq!(values, ("2s".into(), "\"$user\", public".into()));
+            let row = client.query_one("SHOW lock_timeout", &[]).await.unwrap();
+            assert_eq!(row.get::<_, String>(0), "0");
+        }
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn migrations_are_recorded_without_their_role() {
+    migrate_files(vec![], None, async |pool| {
+        pool.get()
+            .await
+            .unwrap()
+            .batch_execute(
+                "DO $$ BEGIN CREATE ROLE pgmt_no_history NOLOGIN; EXCEPTION WHEN duplicate_object THEN NULL; END $$",
+            )
+            .await
+            .unwrap();
+        for mode in [TransactionMode::PerMigration, TransactionMode::All, TransactionMode::None] {
+            let table = format!("history_{mode:?}").to_lowercase();
+            let report = Migrator::new()
+                .sources(vec![file(
+                    "V1__As_role.sql",
+                    "-- pgmt:role = pgmt_no_history\n-- pgmt:statement_timeout = 1ms\nSELECT 1;",
+                )])
+                .history_table(&table)
+                .transaction_mode(mode)
+                .migrate(&pool)
+                .await
+                .unwrap();
+            assert_eq!(report.applied.len(), 1, "{mode:?}");
+        }
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn lock_timeouts_are_retried() {
+    migrate_files(vec![], None, async |pool| {
+        let client = pool.get().await.unwrap();
+        client
+            .batch_execute("CREATE TABLE busy (id INT)")
+            .await
+            .unwrap();
+        let migrator = Migrator::new()
+            .sources(vec![file(
+                "V1__Alter_busy.sql",
+                "ALTER TABLE busy ADD COLUMN name TEXT;",
+            )])
+            .session_settings(SessionSettings {
+                lock_timeout: Some("100ms".to_string()),
+                ..SessionSettings::default()
+            });
+
+        client
+            .batch_execute("BEGIN; LOCK TABLE busy IN ACCESS EXCLUSIVE MODE;")
+            .await
+            .unwrap();
+        let error = migrator.migrate(&pool).await.unwrap_err();
+        assert_eq!(error.sql_state(), Some("55P03"));
+
+        let release = async {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            client.batch_execute("COMMIT").await.unwrap();
+        };
+        let migrator = migrator.lock_timeout_retries(10, Duration::from_millis(100));
+        let migrate = migrator.migrate(&pool);
+        let (report, ()) = tokio::join!(migrate, release);
+        assert_eq!(report.unwrap().applied.len(), 1);
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn invalid_front_matter_is_an_invalid_migration() {
+    migrate_files(vec![], None, async |pool| {
+        let res = Migrator::new()
+            .sources(vec![file(
+                "V1__Bad.sql",
+                "-- pgmt:work_mem = 1GB\nSELECT 1;",
+            )])
+            .migrate(&pool)
+            .await;
+        let Err(Error::InvalidMigrationFilesError(error)) = res else {
+            panic!("Expected InvalidMigrationFilesError, got {res:?}");
+        };
+        assert_eq!(error.files[0].reason, "unknown setting `work_mem`");
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn lint_checks_the_pending_migrations() {
+    migrate_files(vec![], None, async |pool| {
+        let migrations = vec![
+            file(
+                "V1__Create.sql",
+                "CREATE TABLE account (id INT);\nCREATE INDEX a ON account (id);",
+            ),
+            file("V2__Drop.sql", "ALTER TABLE account DROP COLUMN id;"),
+        ];
+        let migrator = Migrator::new().sources(migrations);
+        let report = migrator.lint(&LintOptions::default()).unwrap();
+        assert_eq!(
+            report.checked,
+            vec_of_string!["V1__Create.sql", "V2__Drop.sql"]
+        );
+        assert_eq!(report.findings.len(), 1);
+        assert_eq!(report.findings[0].rule, "drop-column");
+        assert_eq!(report.findings[0].file_path, "migrations/V2__Drop.sql");
+
+        migrator
+            .clone()
+            .target("1")
+            .unwrap()
+            .migrate(&pool)
+            .await
+            .unwrap();
+        let report = migrator
+            .lint_pending(&pool, &LintOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(report.checked, vec_of_string!["V2__Drop.sql"]);
+        let options = LintOptions {
+            allow: vec_of_string!["drop-column"],
+        };
+        assert!(
+            migrator
+                .lint_pending(&pool, &options)
+                .await
+                .unwrap()
+                .is_clean()
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn empty_databases_get_the_baseline_migration() {
+    migrate_files(vec![], None, async |pool| {
+        let migrations = vec![
+            file("V1__Create_a.sql", "CREATE TABLE a (id INT);"),
+            file("V2__Create_b.sql", "CREATE TABLE b (id INT);"),
+            file(
+                "B2__Baseline.sql",
+                "CREATE TABLE a (id INT);\nCREATE TABLE b (id INT);",
+            ),
+            file("V3__Create_c.sql", "CREATE TABLE c (id INT);"),
+        ];
+        let report = Migrator::new()
+            .sources(migrations.clone())
+            .migrate(&pool)
+            .await
+            .unwrap();
+        let applied: Vec<&str> = report.applied.iter().map(|m| m.script.as_str()).collect();
+        assert_eq!(applied, vec!["B2__Baseline.sql", "V3__Create_c.sql"]);
+        let skipped: Vec<&str> = report.skipped.iter().map(|m| m.script.as_str()).collect();
+        assert_eq!(skipped, vec!["V1__Create_a.sql", "V2__Create_b.sql"]);
+        let types: Vec<String> = get_schema_history_rows(&pool)
+            .await
+            .into_iter()
+            .map(|row| row.r#type)
+            .collect();
+        assert_eq!(types, vec_of_string!["SQL_BASELINE", "V"]);
+
+        let report = Migrator::new()
+            .sources(migrations)
+            .migrate(&pool)
+            .await
+            .unwrap();
+        assert!(report.applied.is_empty());
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn databases_past_the_squash_point_stay_valid() {
+    migrate_files(files(), None, async |pool| {
+        // The squashed migrations were moved away, a new one was added.
+        let migrations = vec![
+            file("B3__Baseline.sql", "SELECT 1;"),
+            file("V4__Create_t4.sql", "CREATE TABLE t4 (id INT);"),
+        ];
+        let report = Migrator::new()
+            .sources(migrations.clone())
+            .validate(&pool)
+            .await
+            .unwrap();
+        assert!(report.is_valid(), "{:?}", report.issues);
+        let report = Migrator::new()
+            .sources(migrations)
+            .migrate(&pool)
+            .await
+            .unwrap();
+        let applied: Vec<&str> = report.applied.iter().map(|m| m.script.as_str()).collect();
+        assert_eq!(applied, vec!["V4__Create_t4.sql"]);
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn databases_behind_the_squash_point_need_the_squashed_migrations() {
+    let mut first = files();
+    first.retain(|f| f.file_name == "V1__Create_t1.sql");
+    migrate_files(first, None, async |pool| {
+        // V1 to V3 were squashed into B3 and moved away.
+        let migrations = vec![
+            file("B3__Baseline.sql", "SELECT 1;"),
+            file("V4__Create_t4.sql", "CREATE TABLE t4 (id INT);"),
+        ];
+        let report = Migrator::new()
+            .sources(migrations.clone())
+            .validate(&pool)
+            .await
+            .unwrap();
+        assert!(matches!(
+            report.issues.as_slice(),
+            [ValidationIssue::Squashed(squashed), ValidationIssue::Missing(_)]
+                if squashed.version == "1" && squashed.baseline == "B3__Baseline.sql"
+        ));
+        let res = Migrator::new().sources(migrations).migrate(&pool).await;
+        assert!(
+            matches!(res, Err(Error::SquashedMigrationsError(_))),
+            "{res:?}"
+        );
+        assert!(!get_table_names(&pool).await.contains(&"t4".to_string()));
+
+        // With the squashed migrations restored it migrates.
+        let mut restored = files();
+        restored.push(file("B3__Baseline.sql", "SELECT 1;"));
+        restored.push(file("V4__Create_t4.sql", "CREATE TABLE t4 (id INT);"));
+        let report = Migrator::new()
+            .sources(restored)
+            .migrate(&pool)
+            .await
+            .unwrap();
+        let applied: Vec<&str> = report.applied.iter().map(|m| m.script.as_str()).collect();
+        assert_eq!(
+            applied,
+            vec![
+                "V2__Create_t2.sql",
+                "V3__Create_t3.sql",
+                "V4__Create_t4.sql"
+            ]
+        );
+    })
+    .await;
+}
+
+struct CopyIds {
+    version: &'static str,
+    fail: bool,
+}
+
+#[async_trait]
+impl RustMigration for CopyIds {
+    fn version(&self) -> &str {
+        self.version
+    }
+
+    fn description(&self) -> &str {
+        "Copy ids"
+    }
+
+    fn checksum(&self) -> i32 {
+        42
+    }
+
+    async fn up(&self, transaction: &Transaction<'_>) -> Result<(), BoxError> {
+        transaction
+            .batch_execute("CREATE TABLE copy (id INT); INSERT INTO copy SELECT id FROM t1;")
+            .await?;
+        if self.fail {
+            return Err("copied the wrong ids".into());
+        }
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn rust_migrations_run_between_the_files() {
+    migrate_files(vec![], None, async |pool| {
+        let migrator = Migrator::new()
+            .sources(files())
+            .rust_migration(Arc::new(CopyIds {
+                version: "1.5",
+                fail: false,
+            }));
+        let report = migrator.migrate(&pool).await.unwrap();
+        let applied: Vec<&str> = report.applied.iter().map(|m| m.script.as_str()).collect();
+        assert_eq!(
+            applied,
+            vec![
+                "V1__Create_t1.sql",
+                "V1.5__Copy_ids",
+                "V2__Create_t2.sql",
+                "V3__Create_t3.sql"
+            ]
+        );
+        let rows = get_schema_history_rows(&pool).await;
+        assert_eq!(rows[1].r#type, "RUST");
+        assert_eq!(rows[1].checksum, 42);
+        assert!(migrator.validate(&pool).await.unwrap().is_valid());
+        assert!(migrator.migrate(&pool).await.unwrap().applied.is_empty());
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn failed_rust_migrations_are_rolled_back() {
+    migrate_files(vec![], None, async |pool| {
+        let res = Migrator::new()
+            .sources(files())
+            .rust_migration(Arc::new(CopyIds {
+                version: "1.5",
+                fail: true,
+            }))
+            .migrate(&pool)
+            .await;
+        let Err(error) = res else {
+            panic!("the Rust migration did not fail");
+        };
+        assert!(matches!(error, Error::RustMigrationError(_)));
+        assert_eq!(error.category(), ErrorCategory::Execution);
+        assert_eq!(
+            error.to_string(),
+            "Migration V1.5__Copy_ids failed: copied the wrong ids"
+        );
+        assert_eq!(
+            get_table_names(&pool).await,
+            vec_of_string!["_schema_history", "t1"]
+        );
+
+        let res = Migrator::new()
+            .sources(files())
+            .rust_migration(Arc::new(CopyIds {
+                version: "2",
+                fail: false,
+            }))
+            .migrate(&pool)
+            .await;
+        assert!(matches!(res, Err(Error::DuplicateVersionError(_))));
+    })
+    .await;
+}
+
+const FLYWAY_HISTORY: &str = r#"
+    CREATE TABLE flyway_schema_history (
+        installed_rank INT NOT NULL PRIMARY KEY,
+        version VARCHAR(50),
+        description VARCHAR(200) NOT NULL,
+        type VARCHAR(20) NOT NULL,
+        script VARCHAR(1000) NOT NULL,
+        checksum INTEGER,
+        installed_by VARCHAR(100) NOT NULL,
+        installed_on TIMESTAMP NOT NULL DEFAULT now(),
+        execution_time INTEGER NOT NULL,
+        success BOOLEAN NOT NULL
+    );
+    INSERT INTO flyway_schema_history VALUES
+        (1, '1', '<< Flyway Baseline >>', 'BASELINE', '<< Flyway Baseline >>', NULL, 'flyway', now(), 0, true),
+        (3, '2', 'Create t2', 'SQL', 'V2__Create_t2.sql', $CHECKSUM, 'flyway', now(), 4, true),
+        (4, NULL, 'Views', 'SQL', 'R__Views.sql', 1, 'flyway', now(), 1, true);
+"#;
+
+async fn history_rows(
+    client: &tokio_postgres::Client,
+    table: &str,
+) -> Vec<(i32, Option<String>, String, Option<i32>)> {
+    client
+        .query(
+            &format!("SELECT installed_rank, version, type, checksum FROM {table} ORDER BY 1"),
+            &[],
+        )
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| (row.get(0), row.get(1), row.get(2), row.get(3)))
+        .collect()
+}
+
+fn flyway_checksum(content: &str) -> i32 {
+    let mut hasher = crc32fast::Hasher::new();
+    content
+        .lines()
+        .for_each(|line| hasher.update(line.as_bytes()));
+    hasher.finalize() as i32
+}
+
+#[tokio::test]
+async fn import_the_flyway_history() {
+    migrate_files(vec![], None, async |pool| {
+        let checksum = flyway_checksum("CREATE TABLE t2 (id INT);");
+        let client = pool.get().await.unwrap();
+        client
+            .batch_execute(&FLYWAY_HISTORY.replace("$CHECKSUM", &checksum.to_string()))
+            .await
+            .unwrap();
+
+        let report = Migrator::new()
+            .sources(files())
+            .import_history(&pool, "flyway_schema_history")
+            .await
+            .unwrap();
+        assert_eq!(
+            report.copied,
+            vec_of_string!["<< Flyway Baseline >>", "V2__Create_t2.sql", "R__Views.sql"]
+        );
+        assert_eq!(
+            history_rows(&client, "_schema_history").await,
+            vec![
+                (1, Some("1".to_string()), "BASELINE".to_string(), None),
+                (3, Some("2".to_string()), "V".to_string(), Some(checksum)),
+                (4, None, "R".to_string(), Some(1)),
+            ]
+        );
+
+        // New migrations are ranked after the imported ones.
+        let report = Migrator::new()
+            .sources(files())
+            .migrate(&pool)
+            .await
+            .unwrap();
+        let applied: Vec<&str> = report.applied.iter().map(|m| m.script.as_str()).collect();
+        assert_eq!(applied, vec!["V3__Create_t3.sql"]);
+        assert_eq!(history_rows(&client, "_schema_history").await[3].0, 5);
+
+        let report = Migrator::new()
+            .sources(files())
+            .export_history(&pool, "flyway.history")
+            .await;
+        assert!(matches!(report, Err(Error::TokioPostgres(_))));
+        client.batch_execute("CREATE SCHEMA flyway").await.unwrap();
+        Migrator::new()
+            .sources(files())
+            .export_history(&pool, "flyway.history")
+            .await
+            .unwrap();
+        assert_eq!(
+            history_rows(&client, "flyway.history").await,
+            vec![
+                (1, Some("1".to_string()), "BASELINE".to_string(), None),
+                (3, Some("2".to_string()), "SQL".to_string(), Some(checksum)),
+                (4, None, "SQL".to_string(), Some(1)),
+                (
+                    5,
+                    Some("3".to_string()),
+                    "SQL".to_string(),
+                    Some(flyway_checksum("CREATE TABLE t3 (id INT);"))
+                ),
+            ]
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn changed_files_are_not_imported() {
+    migrate_files(vec![], None, async |pool| {
+        let client = pool.get().await.unwrap();
+        client
+            .batch_execute(&FLYWAY_HISTORY.replace("$CHECKSUM", "123"))
+            .await
+            .unwrap();
+        let res = Migrator::new()
+            .sources(files())
+            .import_history(&pool, "flyway_schema_history")
+            .await;
+        assert!(matches!(res, Err(Error::ChecksumMismatchError(_))));
+        assert_eq!(get_schema_history_rows(&pool).await, vec![]);
+    })
+    .await;
+}
@@ -13,3 +13,54 @@ async fn test_migrating_from_files() {
     })
     .await;
 }
+
+#[tokio::test]
+async fn test_databases_are_copies_of_a_template() {
+    test_migration(vec!["tests/migrations"], None, async |pool| {
+        let client = pool.get().await.unwrap();
+        client.batch_execute("CREATE TABLE scratch ()").await.unwrap();
+        let templates: i64 = client
+            .query_one(
+                "SELECT count(*) FROM pg_database WHERE datname LIKE 'pgmt\\_template\\_%' AND NOT datallowconn",
+                &[],
+            )
+            .await
+            .unwrap()
+            .get(0);
+        assert!(templates > 0);
+    })
+    .await;
+    test_migration(vec!["tests/migrations"], None, async |pool| {
+        assert_eq!(
+            get_table_names(&pool).await,
+            vec_of_string!["_schema_history", "table_1_name", "table_2_name",]
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_databases_are_dropped_when_the_test_panics() {
+    let url = std::sync::Arc::new(std::sync::Mutex::new(String::new()));
+    let test_url = url.clone();
+    let test = tokio::spawn(async move {
+        pgmt_core::test_db(async |_pool, url| {
+            *test_url.lock().unwrap() = url;
+            panic!("assertion failed");
+        })
+        .await
+    });
+    assert!(test.await.unwrap_err().is_panic());
+
+    let url = url.lock().unwrap().clone();
+    let db_name = url.rsplit('/').next().unwrap().to_string();
+    let pool = pgmt_core::connect(std::env::var("PGMT_TEST_DB_URL").unwrap_or_default())
+        .await
+        .unwrap();
+    let client = pool.get().await.unwrap();
+    let exists = client
+        .query_opt("SELECT FROM pg_database WHERE datname = $1", &[&db_name])
+        .await
+        .unwrap();
+    assert!(exists.is_none(), "{db_name} was not dropped");
+}
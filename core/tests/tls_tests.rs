@@ -0,0 +1,84 @@
+#![cfg(any(feature = "rustls", feature = "native-tls"))]
+//! Runs against a Postgres with `ssl = on`, `PGMT_TEST_SSLROOTCERT` names the
+//! certificate of the server, for example the self-signed
+//! `/etc/ssl/certs/ssl-cert-snakeoil.pem` of Debian. The tests needing it are
+//! ignored, run them with `cargo test -- --ignored`.
+
+use pgmt_core::{Error, ErrorCategory, Pool, SslMode, TlsConfig, connect, connect_with_tls};
+
+fn settings() -> (String, String) {
+    dotenvy::dotenv().ok();
+    let root_cert =
+        std::env::var("PGMT_TEST_SSLROOTCERT").expect("PGMT_TEST_SSLROOTCERT is not set");
+    let url = std::env::var("PGMT_TEST_DB_URL").expect("PGMT_TEST_DB_URL is not set");
+    (url, root_cert)
+}
+
+async fn uses_ssl(pool: &Pool) -> Result<bool, Error> {
+    let client = pool.get().await?;
+    let row = client
+        .query_one(
+            "SELECT ssl FROM pg_stat_ssl WHERE pid = pg_backend_pid()",
+            &[],
+        )
+        .await?;
+    Ok(row.get(0))
+}
+
+#[tokio::test]
+#[ignore = "needs PGMT_TEST_SSLROOTCERT"]
+async fn connects_with_the_sslmode_of_the_url() {
+    let (url, root_cert) = settings();
+    let pool = connect(format!("{url}?sslmode=disable")).await.unwrap();
+    assert!(!uses_ssl(&pool).await.unwrap());
+
+    let pool = connect(format!("{url}?sslmode=require")).await.unwrap();
+    assert!(uses_ssl(&pool).await.unwrap());
+
+    let pool = connect(format!("{url}?sslmode=verify-full&sslrootcert={root_cert}"))
+        .await
+        .unwrap();
+    assert!(uses_ssl(&pool).await.unwrap());
+}
+
+#[tokio::test]
+#[ignore = "needs PGMT_TEST_SSLROOTCERT"]
+async fn connects_with_a_tls_config() {
+    let (url, root_cert) = settings();
+    let tls = TlsConfig {
+        mode: SslMode::VerifyCa,
+        root_cert: Some(root_cert),
+        ..TlsConfig::default()
+    };
+    let pool = connect_with_tls(url, tls).await.unwrap();
+    assert!(uses_ssl(&pool).await.unwrap());
+}
+
+#[tokio::test]
+#[ignore = "needs PGMT_TEST_SSLROOTCERT"]
+async fn verify_full_checks_the_host_name() {
+    let (url, root_cert) = settings();
+    // The certificate is issued for localhost, not for its IP address.
+    let url = url.replace("localhost", "127.0.0.1");
+    let pool = connect(format!("{url}?sslmode=verify-ca&sslrootcert={root_cert}"))
+        .await
+        .unwrap();
+    assert!(uses_ssl(&pool).await.unwrap());
+
+    let pool = connect(format!("{url}?sslmode=verify-full&sslrootcert={root_cert}"))
+        .await
+        .unwrap();
+    let error = uses_ssl(&pool).await.unwrap_err();
+    assert_eq!(error.category(), ErrorCategory::Connection);
+}
+
+#[tokio::test]
+async fn missing_certificates_are_configuration_errors() {
+    // Found before connecting, no server is needed.
+    let error = connect(
+        "postgres://localhost/pgmt?sslmode=verify-ca&sslrootcert=/nonexistent.pem".to_string(),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(error.category(), ErrorCategory::Config);
+}
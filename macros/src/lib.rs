@@ -9,3 +9,14 @@ use proc_macro::TokenStream;
 pub fn test(attr: TokenStream, item: TokenStream) -> TokenStream {
     macros::test::test(attr, item)
 }
+
+/// Embeds the migrations found in the locations, relative to the crate's
+/// `Cargo.toml`, into the binary. File names are validated at compile time.
+///
+/// Cargo recompiles when an embedded file changes, add
+/// `println!("cargo:rerun-if-changed=migrations");` to `build.rs` to also pick
+/// up new files.
+#[proc_macro]
+pub fn embed_migrations(input: TokenStream) -> TokenStream {
+    macros::embed::embed_migrations(input)
+}
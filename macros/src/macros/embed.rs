@@ -0,0 +1,77 @@
+use pgmt_core::discovery::read_sql_files;
+use pgmt_core::{DiscoveryOptions, Error};
+use proc_macro::TokenStream;
+use quote::quote;
+use std::path::Path;
+use syn::punctuated::Punctuated;
+use syn::{LitStr, Token, parse::Parser};
+
+pub fn embed_migrations(input: TokenStream) -> TokenStream {
+    let locations = match Punctuated::<LitStr, Token![,]>::parse_terminated.parse(input) {
+        Ok(locations) if !locations.is_empty() => locations,
+        Ok(locations) => {
+            return syn::Error::new_spanned(
+                locations,
+                "Expected one or more migration locations like `\"migrations\"`",
+            )
+            .to_compile_error()
+            .into();
+        }
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let span = locations.first().unwrap().span();
+
+    // Locations are relative to the crate calling the macro, the same way
+    // `include_str!` paths are resolved by cargo.
+    let root = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_string());
+    let root = Path::new(&root);
+    let paths: Vec<String> = locations
+        .iter()
+        .map(|l| root.join(l.value()).to_string_lossy().to_string())
+        .collect();
+
+    let files = match read_sql_files(paths, &DiscoveryOptions::default()) {
+        Ok(files) => files,
+        Err(e) => {
+            return syn::Error::new(span, error_message(&e))
+                .to_compile_error()
+                .into();
+        }
+    };
+
+    let files = files.iter().map(|file| {
+        let file_name = &file.file_name;
+        let absolute_path = &file.file_path;
+        let file_path = Path::new(&file.file_path)
+            .strip_prefix(root)
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|_| file.file_path.clone());
+        quote! {
+            pgmt_core::EmbeddedFile {
+                file_name: #file_name,
+                file_path: #file_path,
+                // include_str! makes cargo recompile when the file changes.
+                content: include_str!(#absolute_path),
+            }
+        }
+    });
+
+    quote! {
+        pgmt_core::EmbeddedMigrations::new(&[#(#files),*])
+    }
+    .into()
+}
+
+fn error_message(error: &Error) -> String {
+    match error {
+        Error::InvalidMigrationFilesError(error) => {
+            let files: Vec<String> = error
+                .files
+                .iter()
+                .map(|f| format!("  {}: {}", f.file_path, f.reason))
+                .collect();
+            format!("Invalid migration files:\n{}", files.join("\n"))
+        }
+        error => format!("Unable to embed migrations: {error}"),
+    }
+}
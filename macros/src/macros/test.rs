@@ -1,19 +1,36 @@
+use pgmt_core::FIXTURE_EXTENSIONS;
 use proc_macro::TokenStream;
 use quote::quote;
 use std::collections::HashMap;
+use std::path::Path;
 use syn::{
+    FnArg, ItemFn, Lit, LitStr, MetaNameValue, PatType, Result, ReturnType, Token, Type,
     parse::{Parse, ParseStream},
-    parse_macro_input, FnArg, ItemFn, Lit, MetaNameValue, Pat, PatIdent, Result, Token, Type,
+    parse_macro_input,
+    punctuated::Punctuated,
 };
 
+/// How tests are kept apart, `isolation = "database"` or `"transaction"`.
+#[derive(PartialEq)]
+enum Isolation {
+    /// A database per test.
+    Database,
+    /// A transaction, rolled back, of a shared database per test.
+    Transaction,
+}
+
 struct PgmtArgs {
     migrations: Option<Vec<String>>,
+    fixtures: Vec<LitStr>,
+    isolation: Isolation,
     placeholders: std::collections::HashMap<String, String>,
 }
 
 impl Parse for PgmtArgs {
     fn parse(input: ParseStream) -> Result<Self> {
         let mut migrations = Vec::new();
+        let mut fixtures = Vec::new();
+        let mut isolation = Isolation::Database;
         let mut placeholders: HashMap<String, String> = HashMap::new();
 
         while !input.is_empty() {
@@ -43,6 +60,36 @@ impl Parse for PgmtArgs {
                         ));
                     }
                 }
+                "fixtures" => match &name_value.value {
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: Lit::Str(lit_str),
+                        ..
+                    }) => fixtures.push(check_fixture(lit_str)?),
+                    value => {
+                        return Err(syn::Error::new_spanned(
+                            value,
+                            "Expected string literal for `fixtures`",
+                        ));
+                    }
+                },
+                "isolation" => {
+                    isolation = match &name_value.value {
+                        syn::Expr::Lit(syn::ExprLit {
+                            lit: Lit::Str(lit_str),
+                            ..
+                        }) if lit_str.value() == "database" => Isolation::Database,
+                        syn::Expr::Lit(syn::ExprLit {
+                            lit: Lit::Str(lit_str),
+                            ..
+                        }) if lit_str.value() == "transaction" => Isolation::Transaction,
+                        value => {
+                            return Err(syn::Error::new_spanned(
+                                value,
+                                "Expected `isolation = \"database\"` or `isolation = \"transaction\"`",
+                            ));
+                        }
+                    }
+                }
                 s if s.starts_with("placeholder_") => {
                     let key = ident
                         .to_string()
@@ -81,11 +128,80 @@ impl Parse for PgmtArgs {
 
         Ok(PgmtArgs {
             migrations,
+            fixtures,
+            isolation,
             placeholders,
         })
     }
 }
 
+/// Fixtures are read when the test runs, relative to the crate like the
+/// migrations, a missing file fails to compile instead.
+fn check_fixture(path: &LitStr) -> Result<LitStr> {
+    let value = path.value();
+    let root = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_string());
+    let extension = Path::new(&value).extension().and_then(|e| e.to_str());
+    if !extension.is_some_and(|e| FIXTURE_EXTENSIONS.contains(&e)) {
+        return Err(syn::Error::new_spanned(
+            path,
+            format!("The fixture {value} is not a .sql or .csv file"),
+        ));
+    }
+    if !Path::new(&root).join(&value).is_file() {
+        return Err(syn::Error::new_spanned(
+            path,
+            format!("The fixture {value} does not exist"),
+        ));
+    }
+    Ok(path.clone())
+}
+
+/// What the test function takes, the test database is passed as these.
+enum TestArgs {
+    Pool,
+    PoolAndUrl,
+    Context,
+}
+
+fn test_args(inputs: &Punctuated<FnArg, Token![,]>) -> Result<TestArgs> {
+    let expected = "Expected `pool: pgmt::Pool`, `pool: pgmt::Pool, url: String` or `context: pgmt::TestContext`";
+    let mut types = Vec::new();
+    for input in inputs {
+        match input {
+            FnArg::Typed(PatType { ty, .. }) => types.push(&**ty),
+            FnArg::Receiver(_) => return Err(syn::Error::new_spanned(input, expected)),
+        }
+    }
+    match types.as_slice() {
+        [ty] if is_named(ty, "TestContext") => Ok(TestArgs::Context),
+        [ty] if is_named(ty, "Pool") => Ok(TestArgs::Pool),
+        [pool, url] if is_named(pool, "Pool") && is_named(url, "String") => {
+            Ok(TestArgs::PoolAndUrl)
+        }
+        _ => Err(syn::Error::new_spanned(inputs, expected)),
+    }
+}
+
+/// Whether the type is named `name`, with or without its path.
+fn is_named(ty: &Type, name: &str) -> bool {
+    match ty {
+        Type::Path(ty) => ty
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == name),
+        _ => false,
+    }
+}
+
+/// Whether the test returns a `Result`, its errors fail the test.
+fn returns_result(output: &ReturnType) -> bool {
+    match output {
+        ReturnType::Type(_, ty) => is_named(ty, "Result"),
+        ReturnType::Default => false,
+    }
+}
+
 pub fn test(attr: TokenStream, item: TokenStream) -> TokenStream {
     let args = parse_macro_input!(attr as PgmtArgs);
     let input_fn = parse_macro_input!(item as ItemFn);
@@ -93,71 +209,103 @@ pub fn test(attr: TokenStream, item: TokenStream) -> TokenStream {
     let vis = &input_fn.vis;
     let asyncness = &input_fn.sig.asyncness;
     let output = &input_fn.sig.output;
+    let inputs = &input_fn.sig.inputs;
+    let attrs = &input_fn.attrs;
 
     let user_block = &input_fn.block;
 
-    let pool_arg = if input_fn.sig.inputs.len() != 1 {
-        return syn::Error::new_spanned(
-            &input_fn.sig.inputs,
-            "Expected exactly one argument: `pool: pgmt::Pool`",
-        )
-        .to_compile_error()
-        .into();
-    } else if let Some(FnArg::Typed(arg)) = input_fn.sig.inputs.first() {
-        match (&*arg.pat, &*arg.ty) {
-            (Pat::Ident(PatIdent { ident, .. }), Type::Path(ty)) => {
-                let arg_name = ident;
-                let ty_path = &ty.path;
-                quote! { #arg_name: #ty_path }
-            }
-            _ => {
-                return syn::Error::new_spanned(
-                    arg,
-                    "Expected argument of the form `pool: pgmt::Pool`",
-                )
-                .to_compile_error()
-                .into();
-            }
-        }
-    } else {
-        return syn::Error::new_spanned(&input_fn.sig.inputs, "Unsupported function signature")
+    let call_args = if args.isolation == Isolation::Transaction {
+        if inputs.len() != 1 {
+            return syn::Error::new_spanned(
+                inputs,
+                "Expected exactly one argument: `transaction: &pgmt::Transaction<'_>`",
+            )
             .to_compile_error()
             .into();
+        }
+        quote! { transaction }
+    } else {
+        match test_args(inputs) {
+            Ok(TestArgs::Pool) => quote! { context.pool },
+            Ok(TestArgs::PoolAndUrl) => quote! { context.pool, context.url },
+            Ok(TestArgs::Context) => quote! { context },
+            Err(error) => return error.to_compile_error().into(),
+        }
     };
-
-    let arg_name = if let FnArg::Typed(arg) = input_fn.sig.inputs.first().unwrap() {
-        if let Pat::Ident(pat_ident) = &*arg.pat {
-            &pat_ident.ident
-        } else {
-            panic!("Expected ident pattern");
+    // A failed test panics so the test database is kept with
+    // PGMT_KEEP_DB=on-failure.
+    let check_result = if returns_result(output) {
+        quote! {
+            if let Err(error) = &result {
+                panic!("Error: {error:?}");
+            }
         }
     } else {
-        panic!("Expected typed argument");
+        quote! {}
     };
 
     let default_migrations: Vec<String> = vec![];
     let migrations = args.migrations.unwrap_or(default_migrations);
     let migrations = migrations.iter().map(|s| quote! { #s.to_string() });
+    let fixtures = args.fixtures;
     let placeholders = args.placeholders;
     let placeholders = placeholders
         .iter()
         .map(|(k, v)| quote! { (#k.to_string(), #v.to_string()) });
 
+    let run = if args.isolation == Isolation::Transaction {
+        quote! {
+            pgmt_core::test_in_transaction(migrations, placeholders, fixtures, async move |transaction| {
+                let result = inner(#call_args).await;
+                #check_result
+                result
+            }).await
+        }
+    } else {
+        quote! {
+            pgmt_core::test_migration_with_fixtures(migrations, placeholders, fixtures, async move |context| {
+                let result = inner(#call_args).await;
+                #check_result
+                result
+            }).await
+        }
+    };
+
     let expanded = quote! {
         #[tokio::test]
+        #(#attrs)*
         #vis #asyncness fn #fn_name() #output {
             let migrations: Vec<String> = vec![#(#migrations),*];
             let placeholders = Some(std::collections::HashMap::from([#(#placeholders),*]));
+            let fixtures: Vec<&str> = vec![#(#fixtures),*];
 
-            async fn inner(#pool_arg) #output {
+            async fn inner(#inputs) #output {
                 #user_block
             }
 
-            pgmt_core::test_migration(migrations, placeholders, async move |#arg_name| {
-                inner(#arg_name).await;
-            }).await;
+            #run
         }
     };
 
     expanded.into()
 }
+
+#[test]
+fn test_args_are_matched_by_type() {
+    let args = |inputs: Punctuated<FnArg, Token![,]>| test_args(&inputs).ok();
+    assert!(matches!(
+        args(syn::parse_quote!(pool: pgmt::Pool)),
+        Some(TestArgs::Pool)
+    ));
+    assert!(matches!(
+        args(syn::parse_quote!(pool: Pool, url: String)),
+        Some(TestArgs::PoolAndUrl)
+    ));
+    assert!(matches!(
+        args(syn::parse_quote!(context: pgmt::TestContext)),
+        Some(TestArgs::Context)
+    ));
+    assert!(args(syn::parse_quote!(url: String)).is_none());
+    assert!(args(syn::parse_quote!(url: String, pool: Pool)).is_none());
+    assert!(args(syn::parse_quote!(pool: &Pool)).is_none());
+}
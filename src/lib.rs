@@ -1,2 +1,5 @@
-pub use pgmt_core::{test_migration, tests_helper, vec_of_string, Pool};
-pub use pgmt_macros::test;
+pub use pgmt_core::{
+    EmbeddedFile, EmbeddedMigrations, Pool, TestContext, Transaction, migrate, test_in_transaction,
+    test_migration, test_migration_with_context, tests_helper, vec_of_string,
+};
+pub use pgmt_macros::{embed_migrations, test};
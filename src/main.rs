@@ -1,11 +1,21 @@
 use clap::Parser;
-use pgmt_cli::{run, Cli};
+use pgmt_cli::{Cli, run};
+use std::process::ExitCode;
+use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     // TODO add dotenv so it can pick up .en files like a grounup
     // dotenv().ok();
     let cli = Cli::parse();
 
-    run(cli).await;
+    // Progress is logged to stderr, set PGMT_LOG=info (or debug) to see it.
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_env("PGMT_LOG").unwrap_or_else(|_| EnvFilter::new("warn")),
+        )
+        .with_writer(std::io::stderr)
+        .init();
+
+    run(cli).await
 }
@@ -0,0 +1,42 @@
+use pgmt::{EmbeddedMigrations, tests_helper::get_table_names, vec_of_string};
+
+static MIGRATIONS: EmbeddedMigrations = pgmt::embed_migrations!("core/tests/migrations");
+
+#[test]
+fn embeds_the_migration_files() {
+    let mut names: Vec<&str> = MIGRATIONS
+        .embedded_files()
+        .iter()
+        .map(|f| f.file_path)
+        .collect();
+    names.sort();
+    assert_eq!(
+        names,
+        vec![
+            "core/tests/migrations/U1.0.0__Drop_table_1_name.sql",
+            "core/tests/migrations/U1.0.1__Drop_table_2_name.sql",
+            "core/tests/migrations/V1.0.0__Create_table_1_name.sql",
+            "core/tests/migrations/V1.0.1__Add_table_2_name.sql",
+        ]
+    );
+    let file = MIGRATIONS
+        .embedded_files()
+        .iter()
+        .find(|f| f.file_name == "U1.0.1__Drop_table_2_name.sql")
+        .unwrap();
+    assert_eq!(
+        file.content,
+        include_str!("../core/tests/migrations/U1.0.1__Drop_table_2_name.sql")
+    );
+}
+
+#[tokio::test]
+async fn migrates_embedded_migrations() {
+    pgmt_core::migrate_files(MIGRATIONS.files(), None, async |pool| {
+        assert_eq!(
+            get_table_names(&pool).await,
+            vec_of_string!["_schema_history", "table_1_name", "table_2_name"]
+        );
+    })
+    .await;
+}
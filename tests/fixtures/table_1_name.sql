@@ -0,0 +1 @@
+INSERT INTO table_1_name (name) VALUES ('${env}');
@@ -35,3 +35,126 @@ async fn test_fuction_2(pool: pgmt::Pool) {
         vec_of_string!["_schema_history", "table_1_name", "table_2_name",]
     );
 }
+
+#[pgmt::test(migrations = "core/tests/migrations")]
+async fn test_returning_a_result(pool: pgmt::Pool) -> Result<(), pgmt_core::Error> {
+    let client = pool.get().await?;
+    client
+        .batch_execute("INSERT INTO table_1_name (name) VALUES ('a')")
+        .await?;
+    Ok(())
+}
+
+#[pgmt::test(migrations = "core/tests/migrations")]
+#[ignore = "run by test_returning_an_error"]
+async fn returns_an_error(pool: pgmt::Pool) -> Result<(), pgmt_core::Error> {
+    let client = pool.get().await?;
+    client.batch_execute("SELECT * FROM missing_table").await?;
+    Ok(())
+}
+
+#[test]
+fn test_returning_an_error() {
+    let panic = std::panic::catch_unwind(returns_an_error).unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+    assert!(message.starts_with("Error: "), "{message}");
+}
+
+#[pgmt::test]
+async fn test_with_the_url(pool: pgmt::Pool, url: String) {
+    let db_name: String = pool
+        .get()
+        .await
+        .unwrap()
+        .query_one("SELECT current_database()", &[])
+        .await
+        .unwrap()
+        .get(0);
+    assert!(url.ends_with(&format!("/{db_name}")), "{url}");
+}
+
+#[pgmt::test(placeholder_env = "env_value")]
+async fn test_with_the_context(context: pgmt::TestContext) {
+    assert!(context.db_name.ends_with("_test_with_the_context"));
+    assert!(context.url.ends_with(&context.db_name));
+    assert_eq!(context.placeholders["env"], "env_value");
+    assert_eq!(
+        get_table_names(&context.pool).await,
+        vec_of_string!["_schema_history"]
+    );
+}
+
+#[pgmt::test(
+    migrations = "core/tests/migrations",
+    fixtures = "tests/fixtures/table_1_name.sql",
+    fixtures = "tests/fixtures/table_2_name.csv",
+    placeholder_env = "env_value"
+)]
+async fn test_with_fixtures(pool: pgmt::Pool) -> Result<(), pgmt_core::Error> {
+    let client = pool.get().await?;
+    let names: Vec<String> = client
+        .query(
+            "SELECT name FROM table_1_name UNION ALL SELECT name FROM table_2_name ORDER BY name",
+            &[],
+        )
+        .await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+    assert_eq!(
+        names,
+        vec_of_string!["env_value", "first", "second, with a comma"]
+    );
+    let history: i64 = client
+        .query_one("SELECT count(*) FROM _schema_history", &[])
+        .await?
+        .get(0);
+    assert_eq!(history, 2);
+    Ok(())
+}
+
+#[pgmt::test(
+    migrations = "core/tests/migrations",
+    fixtures = "tests/fixtures/table_2_name.csv",
+    isolation = "transaction"
+)]
+async fn test_in_a_transaction(
+    transaction: &pgmt::Transaction<'_>,
+) -> Result<(), pgmt_core::Error> {
+    transaction
+        .batch_execute("INSERT INTO table_2_name (name, closed) VALUES ('third', false)")
+        .await?;
+    let count: i64 = transaction
+        .query_one("SELECT count(*) FROM table_2_name", &[])
+        .await?
+        .get(0);
+    // The rows of the other transaction test are rolled back.
+    assert_eq!(count, 3);
+    Ok(())
+}
+
+#[pgmt::test(
+    migrations = "core/tests/migrations",
+    fixtures = "tests/fixtures/table_2_name.csv",
+    isolation = "transaction"
+)]
+async fn test_in_another_transaction(transaction: &pgmt::Transaction<'_>) {
+    transaction
+        .batch_execute("DELETE FROM table_2_name WHERE name = 'first'")
+        .await
+        .unwrap();
+    let count: i64 = transaction
+        .query_one("SELECT count(*) FROM table_2_name", &[])
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(count, 1);
+}
+
+#[pgmt::test(migrations = "core/tests/migrations", isolation = "database")]
+async fn test_in_a_database(pool: pgmt::Pool) {
+    assert_eq!(
+        get_table_names(&pool).await,
+        vec_of_string!["_schema_history", "table_1_name", "table_2_name"]
+    );
+}